@@ -0,0 +1,107 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use falling_sand::sandbox::{
+    clock::{MAX_SPEED, MIN_SPEED},
+    Sandbox, SimulationClock, UpdateMode,
+};
+
+/// Strongest ambient wind set from the interface, in cells per tick.
+const MAX_AMBIENT_WIND: f32 = 3.0;
+
+/// Number of ticks run by the step button and the step key.
+#[derive(Resource)]
+pub struct StepCount(u32);
+
+pub struct ControlsPlugin;
+
+impl Plugin for ControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(StepCount(1))
+            .add_systems(Update, (keyboard_controls, simulation_controls_ui));
+    }
+}
+
+// Space: pause/resume, Period: step, Plus/Minus: double/halve the speed
+pub fn keyboard_controls(
+    keyboard_input: Res<Input<KeyCode>>,
+    step_count: Res<StepCount>,
+    mut clock: ResMut<SimulationClock>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Space) {
+        clock.toggle_pause();
+    }
+    if keyboard_input.just_pressed(KeyCode::Period) {
+        clock.step(step_count.0);
+    }
+    if keyboard_input.any_just_pressed([KeyCode::Equals, KeyCode::NumpadAdd]) {
+        let speed = clock.speed();
+        clock.set_speed(speed * 2.0);
+    }
+    if keyboard_input.any_just_pressed([KeyCode::Minus, KeyCode::NumpadSubtract]) {
+        let speed = clock.speed();
+        clock.set_speed(speed * 0.5);
+    }
+}
+
+pub fn simulation_controls_ui(
+    mut contexts: EguiContexts,
+    mut clock: ResMut<SimulationClock>,
+    mut step_count: ResMut<StepCount>,
+    mut update_mode: ResMut<UpdateMode>,
+    mut sandbox_query: Query<&mut Sandbox>,
+) {
+    let ctx = contexts.ctx_mut();
+    egui::Window::new("Simulation")
+        .anchor(egui::Align2::RIGHT_TOP, [-8.0, 8.0])
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                let label = match clock.is_paused() {
+                    true => "Resume",
+                    false => "Pause",
+                };
+                if ui.button(label).clicked() {
+                    clock.toggle_pause();
+                }
+                if ui.button("Step").clicked() {
+                    clock.step(step_count.0);
+                }
+                ui.add(egui::DragValue::new(&mut step_count.0).clamp_range(1..=1000));
+            });
+
+            let mut speed = clock.speed();
+            if ui
+                .add(
+                    egui::Slider::new(&mut speed, MIN_SPEED..=MAX_SPEED)
+                        .logarithmic(true)
+                        .text("Speed"),
+                )
+                .changed()
+            {
+                clock.set_speed(speed);
+            }
+
+            let mut parallel = *update_mode == UpdateMode::Checkerboard;
+            if ui.checkbox(&mut parallel, "Parallel").changed() {
+                *update_mode = match parallel {
+                    true => UpdateMode::Checkerboard,
+                    false => UpdateMode::SingleThreaded,
+                };
+            }
+
+            if let Ok(mut sandbox) = sandbox_query.get_single_mut() {
+                let mut wind = sandbox.ambient_wind();
+                if ui
+                    .add(
+                        egui::Slider::new(&mut wind.x, -MAX_AMBIENT_WIND..=MAX_AMBIENT_WIND)
+                            .text("Wind"),
+                    )
+                    .changed()
+                {
+                    sandbox.set_ambient_wind(wind);
+                }
+            }
+
+            ui.label(format!("Tick {}", clock.elapsed_ticks()));
+        });
+}
@@ -0,0 +1,140 @@
+use bevy::prelude::*;
+use bevy_egui::{
+    egui::{self, Color32, RichText},
+    EguiContexts, EguiPlugin,
+};
+
+use falling_sand::sandbox::{Sandbox, SandboxSettings};
+
+pub const BRUSH_RADIUS: isize = 4;
+pub const BRUSH_RADIUS_SQR: isize = BRUSH_RADIUS * BRUSH_RADIUS;
+
+/// Selected material, by name so the selection survives a reload of the materials.
+#[derive(Resource, Default)]
+pub struct SelectedParticle {
+    material: String,
+}
+
+pub struct InterationPlugin;
+
+impl Plugin for InterationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(EguiPlugin)
+            .init_resource::<SelectedParticle>()
+            .add_systems(Update, (place_particles, select_particle_ui));
+    }
+}
+
+// should be a multiple of cell size, fits three rows of materials
+pub const PANEL_HEIGHT: f32 = 72.0;
+
+pub fn select_particle_ui(
+    mut contexts: EguiContexts,
+    mut selected: ResMut<SelectedParticle>,
+    sandbox_query: Query<&Sandbox>,
+) {
+    let sandbox = match sandbox_query.get_single() {
+        Ok(sandbox) => sandbox,
+        Err(_) => return,
+    };
+
+    // Falls back to the first material of the palette when the selection is gone
+    if sandbox.materials().id(&selected.material).is_none() {
+        if let Some((_, properties)) = sandbox
+            .materials()
+            .iter()
+            .find(|(_, properties)| properties.selectable)
+        {
+            selected.material = properties.name.clone();
+        }
+    }
+
+    let ctx = contexts.ctx_mut();
+    egui::TopBottomPanel::bottom("bottom_panel")
+        .exact_height(PANEL_HEIGHT)
+        .show(ctx, |ui| {
+            // Materials wrap onto more rows, scrolling once they don't fit in the panel
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                ui.horizontal_wrapped(|ui| {
+                    for (_, properties) in sandbox.materials().iter() {
+                        if !properties.selectable {
+                            continue;
+                        }
+
+                        let color = properties.colors.first().copied().unwrap_or(Color::WHITE);
+                        let fill = Color32::from_rgb(
+                            (color.r() * 255.0) as u8,
+                            (color.g() * 255.0) as u8,
+                            (color.b() * 255.0) as u8,
+                        );
+                        if ui
+                            .add(
+                                egui::Button::new(
+                                    RichText::from(properties.name.as_str()).color(Color32::BLACK),
+                                )
+                                .fill(fill),
+                            )
+                            .clicked()
+                        {
+                            selected.material = properties.name.clone();
+                        }
+                    }
+                });
+            });
+        });
+}
+
+pub fn place_particles(
+    mut sandbox_query: Query<&mut Sandbox>,
+    window_query: Query<&Window>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    mouse_button_input: Res<Input<MouseButton>>,
+    selected: Res<SelectedParticle>,
+    settings: Res<SandboxSettings>,
+) {
+    let (camera, camera_transform) = camera_query.single();
+    let window: &Window = window_query.get_single().unwrap();
+    let mut sandbox = sandbox_query.single_mut();
+    let material = match sandbox.materials().id(&selected.material) {
+        Some(material) => material,
+        None => return,
+    };
+
+    if let Some(world_pos) = window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world(camera_transform, cursor))
+        .map(|ray| ray.origin.truncate())
+    {
+        let (cx, cy) = match settings.world_to_cell(world_pos) {
+            Some(cell) => cell,
+            None => return,
+        };
+
+        for x_offset in -BRUSH_RADIUS..=BRUSH_RADIUS {
+            for y_offset in -BRUSH_RADIUS..=BRUSH_RADIUS {
+                let x = cx.saturating_add_signed(x_offset);
+                let y = cy.saturating_add_signed(y_offset);
+                if sandbox.out_of_bounds_usize(x, y) {
+                    return;
+                }
+                let dist_sqr = x_offset * x_offset + y_offset * y_offset;
+                if dist_sqr <= BRUSH_RADIUS_SQR {
+                    if sandbox.out_of_bounds_usize(cx, cy) {
+                        return;
+                    }
+
+                    if mouse_button_input.pressed(MouseButton::Left)
+                        && sandbox.checked_get(x, y).is_none()
+                    {
+                        let particle = sandbox.get_particle(material);
+                        sandbox.set(x, y, Some(particle));
+                    } else if mouse_button_input.pressed(MouseButton::Right)
+                        && sandbox.get(x, y).is_some()
+                    {
+                        sandbox.set(x, y, None);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,10 @@
+//! Falling sand simulation core: the particle grid, the materials and the
+//! stepping logic, with a Bevy plugin that runs it and, with the `render` feature, one that
+//! renders the sandbox to a sprite.
+
+pub mod sandbox;
+mod utils;
+
+#[cfg(feature = "render")]
+pub use sandbox::SandboxRenderPlugin;
+pub use sandbox::{SandboxPlugin, SandboxSettings};
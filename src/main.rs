@@ -1,10 +1,13 @@
 use bevy::{prelude::*, render::camera::ScalingMode, window::PresentMode};
+use falling_sand::{SandboxPlugin, SandboxRenderPlugin, SandboxSettings};
 
-mod sandbox;
-mod utils;
-use sandbox::SandboxPlugin;
+mod controls;
+mod interaction;
+use controls::ControlsPlugin;
+use interaction::{InterationPlugin, PANEL_HEIGHT};
 
 pub const RESOLUTION: (f32, f32) = (1280.0, 960.0);
+pub const CELL_SIZE: f32 = 4.0;
 
 fn main() {
     App::new()
@@ -24,7 +27,19 @@ fn main() {
         .insert_resource(Msaa::Off)
         .add_systems(Startup, spawn_camera)
         .add_systems(Update, bevy::window::close_on_esc)
-        .add_plugins(SandboxPlugin)
+        .add_plugins(SandboxPlugin {
+            settings: SandboxSettings {
+                resolution: Vec2::new(RESOLUTION.0, RESOLUTION.1 - PANEL_HEIGHT),
+                cell_size: CELL_SIZE,
+                translation: Vec2::new(0.0, PANEL_HEIGHT * 0.5),
+                seed: std::env::var("SANDBOX_SEED")
+                    .ok()
+                    .and_then(|seed| seed.parse().ok())
+                    .unwrap_or_else(rand::random),
+                ..default()
+            },
+        })
+        .add_plugins((SandboxRenderPlugin, InterationPlugin, ControlsPlugin))
         .run();
 }
 
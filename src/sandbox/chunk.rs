@@ -0,0 +1,128 @@
+pub const CHUNK_SIZE: usize = 64;
+/// How far outside of its chunk a particle can reach when chunks are updated in parallel.
+/// Chunks updated during the same pass are two chunks apart, so their reach never overlaps.
+pub const CHUNK_MARGIN: usize = CHUNK_SIZE / 2;
+
+/// Inclusive bounds of the cells that need to be stepped in a chunk.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DirtyRect {
+    pub min_x: usize,
+    pub min_y: usize,
+    pub max_x: usize,
+    pub max_y: usize,
+}
+
+impl DirtyRect {
+    pub fn new(min_x: usize, min_y: usize, max_x: usize, max_y: usize) -> Self {
+        Self {
+            min_x,
+            min_y,
+            max_x,
+            max_y,
+        }
+    }
+
+    pub fn union(&self, other: &DirtyRect) -> Self {
+        Self {
+            min_x: self.min_x.min(other.min_x),
+            min_y: self.min_y.min(other.min_y),
+            max_x: self.max_x.max(other.max_x),
+            max_y: self.max_y.max(other.max_y),
+        }
+    }
+
+    /// Grows the rect by `margin` on every side, clipped to a `width` x `height` grid.
+    pub fn expand(&self, margin: usize, width: usize, height: usize) -> Self {
+        Self {
+            min_x: self.min_x.saturating_sub(margin),
+            min_y: self.min_y.saturating_sub(margin),
+            max_x: (self.max_x + margin).min(width - 1),
+            max_y: (self.max_y + margin).min(height - 1),
+        }
+    }
+
+    #[inline]
+    pub fn width(&self) -> usize {
+        self.max_x - self.min_x + 1
+    }
+
+    #[inline]
+    pub fn height(&self) -> usize {
+        self.max_y - self.min_y + 1
+    }
+
+    pub fn intersection(&self, other: &DirtyRect) -> Option<Self> {
+        let rect = Self {
+            min_x: self.min_x.max(other.min_x),
+            min_y: self.min_y.max(other.min_y),
+            max_x: self.max_x.min(other.max_x),
+            max_y: self.max_y.min(other.max_y),
+        };
+
+        if rect.min_x > rect.max_x || rect.min_y > rect.max_y {
+            return None;
+        }
+        Some(rect)
+    }
+}
+
+/// A fixed-size area of the sandbox that keeps track of the cells that changed.
+///
+/// Changes made during a tick are accumulated in `next`, and become the area
+/// to step (`current`) once the following tick starts.
+#[derive(Clone)]
+pub struct Chunk {
+    bounds: DirtyRect,
+    current: Option<DirtyRect>,
+    next: Option<DirtyRect>,
+}
+
+impl Chunk {
+    pub fn new(bounds: DirtyRect) -> Self {
+        Self {
+            bounds,
+            current: None,
+            next: None,
+        }
+    }
+
+    #[inline]
+    pub fn bounds(&self) -> DirtyRect {
+        self.bounds
+    }
+
+    #[inline]
+    pub fn dirty_rect(&self) -> Option<DirtyRect> {
+        self.current
+    }
+
+    #[inline]
+    pub fn next_dirty_rect(&self) -> Option<DirtyRect> {
+        self.next
+    }
+
+    /// Extends the area to step next tick, clipped to the chunk bounds.
+    pub fn wake(&mut self, rect: &DirtyRect) {
+        let rect = match self.bounds.intersection(rect) {
+            Some(r) => r,
+            None => return,
+        };
+
+        self.next = match self.next {
+            Some(next) => Some(next.union(&rect)),
+            None => Some(rect),
+        };
+    }
+
+    /// Adds the area woken in `other` to the area to step next tick.
+    pub fn merge(&mut self, other: &Chunk) {
+        if let Some(rect) = other.next {
+            self.wake(&rect);
+        }
+    }
+
+    #[inline]
+    pub fn swap_rects(&mut self) {
+        self.current = self.next.take();
+    }
+}
@@ -0,0 +1,107 @@
+use bevy::prelude::*;
+
+pub const MIN_SPEED: f32 = 0.25;
+pub const MAX_SPEED: f32 = 8.0;
+/// Caps the ticks run during a single frame, so a slow frame can't snowball into slower ones.
+pub const MAX_TICKS_PER_FRAME: u32 = 64;
+
+/// Fixed timestep clock driving the simulation, independently of the frame rate.
+#[derive(Resource, Debug)]
+pub struct SimulationClock {
+    tick_duration: f32,
+    speed: f32,
+    paused: bool,
+    accumulator: f32,
+    pending_steps: u32,
+    ticks_to_run: u32,
+    elapsed_ticks: u64,
+}
+
+impl SimulationClock {
+    pub fn new(tick_rate: f32) -> Self {
+        Self {
+            tick_duration: 1.0 / tick_rate,
+            speed: 1.0,
+            paused: false,
+            accumulator: 0.0,
+            pending_steps: 0,
+            ticks_to_run: 0,
+            elapsed_ticks: 0,
+        }
+    }
+
+    #[inline]
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+        self.accumulator = 0.0;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn toggle_pause(&mut self) {
+        match self.paused {
+            true => self.resume(),
+            false => self.pause(),
+        }
+    }
+
+    /// Runs exactly `ticks` more ticks, even while paused.
+    pub fn step(&mut self, ticks: u32) {
+        self.pending_steps = self.pending_steps.saturating_add(ticks);
+    }
+
+    #[inline]
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    /// Sets the speed multiplier, clamped between `MIN_SPEED` and `MAX_SPEED`.
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.clamp(MIN_SPEED, MAX_SPEED);
+    }
+
+    /// Number of ticks run since the start of the simulation.
+    #[inline]
+    pub fn elapsed_ticks(&self) -> u64 {
+        self.elapsed_ticks
+    }
+
+    /// Number of ticks to run during the current frame.
+    #[inline]
+    pub fn ticks_to_run(&self) -> u32 {
+        self.ticks_to_run
+    }
+
+    /// Accumulates the time of a frame, and computes how many ticks it covers.
+    /// Requested steps that don't fit in the frame are kept for the next ones.
+    pub fn advance(&mut self, delta_seconds: f32) {
+        let mut ticks = 0;
+        if !self.paused {
+            self.accumulator += delta_seconds * self.speed;
+            ticks = (self.accumulator / self.tick_duration) as u32;
+            self.accumulator -= ticks as f32 * self.tick_duration;
+
+            if ticks > MAX_TICKS_PER_FRAME {
+                ticks = MAX_TICKS_PER_FRAME;
+                self.accumulator = 0.0;
+            }
+        }
+
+        let steps = self.pending_steps.min(MAX_TICKS_PER_FRAME - ticks);
+        self.pending_steps -= steps;
+        ticks += steps;
+
+        self.ticks_to_run = ticks;
+        self.elapsed_ticks += ticks as u64;
+    }
+}
+
+pub fn advance_clock(time: Res<Time>, mut clock: ResMut<SimulationClock>) {
+    clock.advance(time.delta_seconds());
+}
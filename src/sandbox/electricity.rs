@@ -0,0 +1,68 @@
+use super::particle::Electric;
+use super::sandbox::Sandbox;
+
+/// Sends a pulse of current from every power source through the conductors connected to it:
+/// - powered conductors heat up by their `heat`,
+/// - burnable insulators touching a powered particle are heated to the point where they
+///   ignite, and explosive ones have their fuse lit.
+///
+/// Must run on the whole sandbox, before the chunks are updated.
+pub fn update_electricity(sandbox: &mut Sandbox) {
+    // Current is sent again every tick, particles disconnected since the last one lose it
+    let mut powered = sandbox.take_powered();
+    let mut stack: Vec<(usize, usize)> = sandbox.power_sources().iter().copied().collect();
+    for &(x, y) in stack.iter() {
+        powered.mark(sandbox.to_index(x, y), 0);
+    }
+
+    while let Some((x, y)) = stack.pop() {
+        let particle = *sandbox.get(x, y).unwrap();
+        if let Some(Electric::Conductor { heat }) = sandbox.properties(particle.material).electric {
+            if heat != 0 {
+                sandbox.get_mut(x, y).unwrap().temperature += heat;
+                sandbox.wake(x, y);
+            }
+        }
+
+        for (neighbor_x, neighbor_y) in [
+            (x.overflowing_sub(1).0, y),
+            (x + 1, y),
+            (x, y.overflowing_sub(1).0),
+            (x, y + 1),
+        ] {
+            let neighbor = match sandbox.checked_get(neighbor_x, neighbor_y) {
+                Some(neighbor) => *neighbor,
+                None => continue,
+            };
+            let index = sandbox.to_index(neighbor_x, neighbor_y);
+            if powered.is_marked(index) {
+                continue;
+            }
+
+            let properties = sandbox.properties(neighbor.material);
+            if neighbor.conducts(properties) {
+                powered.mark(index, 0);
+                stack.push((neighbor_x, neighbor_y));
+                continue;
+            }
+
+            // Sparks ignite the insulators around the circuit. Explosives have their fuse lit
+            // rather than being heated, their heat would leak away before they go off
+            if let Some(burnable) = properties.burnable.filter(|_| !neighbor.burning) {
+                let ignition = burnable.burn_temperature + 1;
+                if neighbor.temperature < ignition {
+                    sandbox.get_mut(neighbor_x, neighbor_y).unwrap().temperature = ignition;
+                    sandbox.wake(neighbor_x, neighbor_y);
+                }
+            } else if properties
+                .temperature
+                .is_some_and(|temperature| temperature.explosion.is_some())
+                && neighbor.counter == 0
+            {
+                sandbox.get_mut(neighbor_x, neighbor_y).unwrap().counter = 1;
+                sandbox.wake(neighbor_x, neighbor_y);
+            }
+        }
+    }
+    sandbox.set_powered(powered);
+}
@@ -0,0 +1,87 @@
+use rand::Rng;
+
+use super::sandbox::Sandbox;
+
+/// Spawns the material of an emitter into its empty neighbors. An emitter without a material,
+/// a clone block, copies the first particle that touches it.
+pub fn step_emitter(x: usize, y: usize, sandbox: &mut Sandbox) -> bool {
+    let particle = *sandbox.get(x, y).unwrap();
+    let emitter = match sandbox.properties(particle.material).emitter {
+        Some(emitter) => emitter,
+        None => return false,
+    };
+
+    let neighbors = [
+        (x, y.overflowing_sub(1).0),
+        (x.overflowing_sub(1).0, y),
+        (x + 1, y),
+        (x, y + 1),
+    ];
+
+    let material = match emitter.material.or(particle.copied()) {
+        Some(material) => material,
+        None => {
+            // Emitters and drains can't be copied, or clone blocks would copy each other
+            let copied = neighbors.into_iter().find_map(|(neighbor_x, neighbor_y)| {
+                let neighbor = sandbox.checked_get(neighbor_x, neighbor_y)?;
+                let properties = sandbox.properties(neighbor.material);
+                (properties.emitter.is_none() && !properties.drain).then_some(neighbor.material)
+            });
+            match copied {
+                Some(material) => {
+                    sandbox.get_mut(x, y).unwrap().set_copied(Some(material));
+                    material
+                }
+                None => return false,
+            }
+        }
+    };
+
+    let mut blocked = true;
+    for (neighbor_x, neighbor_y) in neighbors {
+        if sandbox.checked_get(neighbor_x, neighbor_y).is_some()
+            || sandbox.out_of_bounds_usize(neighbor_x, neighbor_y)
+        {
+            continue;
+        }
+        blocked = false;
+
+        if sandbox.rng().gen_bool(emitter.rate as f64) {
+            let emitted = sandbox.get_particle(material);
+            sandbox.set(neighbor_x, neighbor_y, Some(emitted));
+        }
+    }
+
+    // Stays awake until surrounded, a neighbor moving away wakes it up again
+    if !blocked {
+        sandbox.wake(x, y);
+    }
+    false
+}
+
+/// Removes the particles touching a drain, but for the drains, emitters and clone blocks, so
+/// a drain next to a source doesn't eat it.
+pub fn step_drain(x: usize, y: usize, sandbox: &mut Sandbox) -> bool {
+    let material = sandbox.get(x, y).unwrap().material;
+    if !sandbox.properties(material).drain {
+        return false;
+    }
+
+    for (neighbor_x, neighbor_y) in [
+        (x.overflowing_sub(1).0, y),
+        (x + 1, y),
+        (x, y.overflowing_sub(1).0),
+        (x, y + 1),
+    ] {
+        let drained = sandbox
+            .checked_get(neighbor_x, neighbor_y)
+            .is_some_and(|neighbor| {
+                let properties = sandbox.properties(neighbor.material);
+                properties.emitter.is_none() && !properties.drain
+            });
+        if drained {
+            sandbox.set(neighbor_x, neighbor_y, None);
+        }
+    }
+    false
+}
@@ -0,0 +1,78 @@
+use rand::Rng;
+
+use super::sandbox::Sandbox;
+
+/// Health a flame takes from a burning neighbor every tick, the fuel it feeds on.
+const FUEL_PER_TICK: i32 = 1;
+
+/// Feeds flames on their burning neighbors and spreads them to the flammable ones. Flames go
+/// out without air or next to a material that extinguishes them. Returns true if the particle
+/// was removed.
+pub fn step_flame(x: usize, y: usize, sandbox: &mut Sandbox) -> bool {
+    let particle = *sandbox.get(x, y).unwrap();
+    let properties = sandbox.properties(particle.material);
+    if !properties.flame {
+        return false;
+    }
+    let max_health = properties.health + properties.health_variation;
+
+    let (air, wet) = exposure(x, y, sandbox);
+    if !air || wet {
+        sandbox.set(x, y, None);
+        return true;
+    }
+
+    let mut health = particle.health;
+    for (neighbor_x, neighbor_y) in [
+        (x.overflowing_sub(1).0, y),
+        (x + 1, y),
+        (x, y.overflowing_sub(1).0),
+        (x, y + 1),
+    ] {
+        let neighbor = match sandbox.checked_get(neighbor_x, neighbor_y) {
+            Some(neighbor) => *neighbor,
+            None => continue,
+        };
+        let burnable = match sandbox.properties(neighbor.material).burnable {
+            Some(burnable) => burnable,
+            None => continue,
+        };
+
+        if neighbor.burning {
+            // The fuel keeps enough health to leave its residue once burned out
+            if neighbor.health > FUEL_PER_TICK && health < max_health {
+                sandbox.get_mut(neighbor_x, neighbor_y).unwrap().health -= FUEL_PER_TICK;
+                health += FUEL_PER_TICK;
+            }
+        } else if sandbox.rng().gen_bool(burnable.flammability as f64) {
+            let neighbor = sandbox.get_mut(neighbor_x, neighbor_y).unwrap();
+            neighbor.temperature = neighbor.temperature.max(burnable.burn_temperature + 1);
+            sandbox.wake(neighbor_x, neighbor_y);
+        }
+    }
+
+    sandbox.get_mut(x, y).unwrap().health = health;
+    false
+}
+
+/// Whether the particle touches air, an empty cell, an oxidizer or a flame burning in it, and
+/// whether it touches a material that extinguishes fire.
+pub fn exposure(x: usize, y: usize, sandbox: &Sandbox) -> (bool, bool) {
+    let (mut air, mut wet) = (false, false);
+    for (neighbor_x, neighbor_y) in [
+        (x.overflowing_sub(1).0, y),
+        (x + 1, y),
+        (x, y.overflowing_sub(1).0),
+        (x, y + 1),
+    ] {
+        match sandbox.checked_get(neighbor_x, neighbor_y) {
+            Some(neighbor) => {
+                let properties = sandbox.properties(neighbor.material);
+                air |= properties.oxidizer || properties.flame;
+                wet |= properties.extinguishes;
+            }
+            None => air |= !sandbox.out_of_bounds_usize(neighbor_x, neighbor_y),
+        }
+    }
+    (air, wet)
+}
@@ -0,0 +1,213 @@
+use rand::Rng;
+
+use super::particle::{Growth, MovementType};
+use super::sandbox::Sandbox;
+
+/// Water a growing particle gains by absorbing one water particle.
+const WATER_PER_PARTICLE: i32 = 4;
+/// Distance from a seed at which water makes it sprout.
+const SPROUT_RANGE: i32 = 2;
+
+/// Sprouts seeds, and grows the particles with a `Growth`. Returns true if the particle was
+/// replaced.
+pub fn step_growth(x: usize, y: usize, sandbox: &mut Sandbox) -> bool {
+    let material = sandbox.get(x, y).unwrap().material;
+    let properties = sandbox.properties(material);
+
+    if properties.sprout.is_some() {
+        return step_sprout(x, y, sandbox);
+    }
+
+    if let Some(growth) = properties.growth {
+        step_plant(x, y, growth, sandbox);
+    }
+    false
+}
+
+fn step_sprout(x: usize, y: usize, sandbox: &mut Sandbox) -> bool {
+    let material = sandbox.get(x, y).unwrap().material;
+    let sprout = sandbox.properties(material).sprout.as_ref().unwrap();
+
+    let on_soil = sandbox
+        .checked_get(x, y.overflowing_sub(1).0)
+        .is_some_and(|below| sprout.soil.contains(&below.material));
+    if !on_soil {
+        return false;
+    }
+
+    let (plant, water, probability) = (sprout.material, sprout.water, sprout.probability);
+
+    // Water flowing around doesn't always wake the seed, so it stays awake while on soil
+    sandbox.wake(x, y);
+
+    let mut source = None;
+    'search: for offset_y in -SPROUT_RANGE..=SPROUT_RANGE {
+        for offset_x in -SPROUT_RANGE..=SPROUT_RANGE {
+            let (water_x, water_y) = (x as i32 + offset_x, y as i32 + offset_y);
+            if sandbox
+                .checked_get_i32(water_x, water_y)
+                .is_some_and(|particle| particle.material == water)
+            {
+                source = Some((water_x as usize, water_y as usize));
+                break 'search;
+            }
+        }
+    }
+
+    let (water_x, water_y) = match source {
+        Some(source) => source,
+        None => return false,
+    };
+
+    if !sandbox.rng().gen_bool(probability as f64) {
+        return false;
+    }
+
+    let mut sprouted = sandbox.get_particle(plant);
+    sprouted.counter = WATER_PER_PARTICLE;
+    sandbox.set(water_x, water_y, None);
+    sandbox.set(x, y, Some(sprouted));
+    true
+}
+
+/// Absorbs touching water, shares it with the growing neighbors above or holding less, and spends
+/// it to grow upward, or along the surfaces the particle clings to. Only the tips of a plant,
+/// without any growing particle above them, grow.
+fn step_plant(x: usize, y: usize, growth: Growth, sandbox: &mut Sandbox) {
+    let neighbors = [
+        (x.overflowing_sub(1).0, y),
+        (x + 1, y),
+        (x, y.overflowing_sub(1).0),
+        (x, y + 1),
+        (x.overflowing_sub(1).0, y.overflowing_sub(1).0),
+        (x + 1, y.overflowing_sub(1).0),
+        (x.overflowing_sub(1).0, y + 1),
+        (x + 1, y + 1),
+    ];
+    let mut changed = false;
+
+    if let Some(water) = growth.water {
+        if sandbox.get(x, y).unwrap().counter + WATER_PER_PARTICLE <= growth.capacity {
+            let source = neighbors.into_iter().find(|&(neighbor_x, neighbor_y)| {
+                sandbox
+                    .checked_get(neighbor_x, neighbor_y)
+                    .is_some_and(|neighbor| neighbor.material == water)
+            });
+
+            if let Some((water_x, water_y)) = source {
+                sandbox.set(water_x, water_y, None);
+                sandbox.get_mut(x, y).unwrap().counter += WATER_PER_PARTICLE;
+                changed = true;
+            }
+        }
+    }
+
+    let mut tip = true;
+    for (neighbor_x, neighbor_y) in neighbors {
+        let own_water = sandbox.get(x, y).unwrap().counter;
+        let neighbor = match sandbox.checked_get(neighbor_x, neighbor_y) {
+            Some(neighbor) => *neighbor,
+            None => continue,
+        };
+        let capacity = match sandbox.properties(neighbor.material).growth {
+            Some(neighbor_growth) => neighbor_growth.capacity,
+            None => continue,
+        };
+        if neighbor_y > y {
+            tip = false;
+        }
+
+        // Water rises toward the tips, and evens out with the other neighbors
+        let share = match neighbor_y > y {
+            true => own_water > 0,
+            false => neighbor.counter + 1 < own_water,
+        };
+        if share && neighbor.counter < capacity {
+            sandbox.get_mut(neighbor_x, neighbor_y).unwrap().counter += 1;
+            sandbox.get_mut(x, y).unwrap().counter -= 1;
+            sandbox.wake(neighbor_x, neighbor_y);
+            changed = true;
+        }
+    }
+
+    let water = sandbox.get(x, y).unwrap().counter;
+    let tip = tip && water > 0;
+    if changed || tip {
+        sandbox.wake(x, y);
+    }
+
+    if !tip || !sandbox.rng().gen_bool(growth.rate as f64) {
+        return;
+    }
+
+    let target = match grow_target(x, y, sandbox) {
+        Some(target) => target,
+        None => return,
+    };
+
+    let material = match growth.bloom {
+        Some(bloom) if sandbox.rng().gen_bool(bloom.probability as f64) => bloom.material,
+        _ => Some(growth.material),
+    };
+    let material = match material {
+        Some(material) => material,
+        None => return,
+    };
+
+    // Growing costs one water, the new particle takes half of what is left
+    let remaining = water - 1;
+    let mut grown = sandbox.get_particle(material);
+    grown.counter = remaining / 2;
+    sandbox.get_mut(x, y).unwrap().counter = remaining - grown.counter;
+    sandbox.set(target.0, target.1, Some(grown));
+}
+
+/// Empty cell to grow into: mostly straight up, sometimes up to a side, and otherwise to a
+/// side cell touching something to cling to.
+fn grow_target(x: usize, y: usize, sandbox: &mut Sandbox) -> Option<(usize, usize)> {
+    let is_free = |x: usize, y: usize, sandbox: &Sandbox| {
+        !sandbox.out_of_bounds_usize(x, y) && sandbox.get(x, y).is_none()
+    };
+
+    let upward = match sandbox.rng().gen_range(0..8) {
+        0 => (x.overflowing_sub(1).0, y + 1),
+        1 => (x + 1, y + 1),
+        _ => (x, y + 1),
+    };
+    if is_free(upward.0, upward.1, sandbox) {
+        return Some(upward);
+    }
+
+    let mut sides = [
+        (x.overflowing_sub(1).0, y + 1),
+        (x + 1, y + 1),
+        (x.overflowing_sub(1).0, y),
+        (x + 1, y),
+    ];
+    if sandbox.rng().gen_bool(0.5) {
+        sides.reverse();
+    }
+
+    sides.into_iter().find(|&(side_x, side_y)| {
+        is_free(side_x, side_y, sandbox)
+            && [
+                (side_x.overflowing_sub(1).0, side_y),
+                (side_x + 1, side_y),
+                (side_x, side_y.overflowing_sub(1).0),
+                (side_x, side_y + 1),
+            ]
+            .into_iter()
+            .any(|(support_x, support_y)| {
+                sandbox
+                    .checked_get(support_x, support_y)
+                    .is_some_and(|support| {
+                        let properties = sandbox.properties(support.material);
+                        properties.growth.is_none()
+                            && matches!(
+                                properties.movement_type,
+                                MovementType::Solid | MovementType::Powder
+                            )
+                    })
+            })
+    })
+}
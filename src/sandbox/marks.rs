@@ -0,0 +1,48 @@
+/// Marks put on the cells during a pass over the sandbox, like the cells a flood fill visited.
+/// The buffer is kept between passes so it isn't allocated again, and the marks of the previous
+/// pass are cleared in constant time by starting a new generation.
+#[derive(Default)]
+pub struct CellMarks {
+    generation: u32,
+    /// Generation each cell was last marked in, with the value it was marked with.
+    marks: Vec<(u32, u32)>,
+}
+
+impl CellMarks {
+    /// Removes every mark, for a grid of `len` cells. Must be called before the first pass.
+    pub fn clear(&mut self, len: usize) {
+        if self.marks.len() != len || self.generation == u32::MAX {
+            self.marks.clear();
+            self.marks.resize(len, (0, 0));
+            self.generation = 0;
+        }
+        // Cells are never marked with the generation 0
+        self.generation += 1;
+    }
+
+    /// Value the cell was marked with during this pass.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<u32> {
+        match self.marks.get(index) {
+            Some(&(generation, value)) if generation == self.generation => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Whether the cell was marked during this pass, cells are never marked before the marks
+    /// are cleared for the first time.
+    #[inline]
+    pub fn is_marked(&self, index: usize) -> bool {
+        self.get(index).is_some()
+    }
+
+    #[inline]
+    pub fn mark(&mut self, index: usize, value: u32) {
+        self.marks[index] = (self.generation, value);
+    }
+
+    #[inline]
+    pub fn unmark(&mut self, index: usize) {
+        self.marks[index].0 = 0;
+    }
+}
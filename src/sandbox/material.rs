@@ -0,0 +1,202 @@
+use bevy::render::color::Color;
+use rand::Rng;
+
+use super::material_asset::MaterialsAsset;
+use super::particle::*;
+
+/// Static properties shared by every particle of a material.
+#[derive(Default)]
+pub struct MaterialProperties {
+    pub name: String,
+    /// Whether the material is shown in the palette.
+    pub selectable: bool,
+    /// A particle color is picked from these on spawn.
+    pub colors: Vec<Color>,
+    pub color_variation: f32,
+    pub health: i32,
+    /// Spawned particles get an additional random health, up to this value (excluded).
+    pub health_variation: i32,
+    pub lifespan: Option<LifeSpan>,
+    pub density: Density,
+    pub movement_type: MovementType,
+    pub spread_rate: i32,
+    /// Chance a liquid skips moving sideways on a tick, it also shortens its spread.
+    pub viscosity: f32,
+    /// Chance a liquid refuses to move sideways onto a surface without any particle of its
+    /// material above or below, which would thin it into a one cell thick film.
+    pub surface_tension: f32,
+    pub use_gravity: bool,
+    /// Share of the difference with the wind velocity a particle catches up every tick.
+    pub drag: f32,
+    /// Whether connected solid particles of rigid materials move together as a body.
+    pub rigid: bool,
+    /// Whether the material anchors the solids connected to it, like the border of the sandbox.
+    pub anchored: bool,
+    /// Debris a solid crumbles into once it isn't connected to an anchor anymore.
+    pub crumble: Option<MaterialId>,
+    pub spawn_velocity: Option<SpawnVelocity>,
+    /// Acids corrode their neighbors, bases have a negative acidity and neutralize acids.
+    pub acidity: Option<Acidity>,
+    /// What an acid or a base turns into once neutralized, removed without one.
+    pub neutralized: Option<ParticleReplacement>,
+    pub corrodable: bool,
+    /// Share of the acidity of an acid a corrodable particle withstands, from 0 to 1.
+    pub corrosion_resistance: f32,
+    /// What a corrodable particle leaves once dissolved, removed without one.
+    pub corrosion_residue: Option<ParticleReplacement>,
+    pub temperature: Option<Temperature>,
+    pub temperature_changer: Option<TemperatureChanger>,
+    /// Fraction of the temperature difference exchanged with a neighbor every tick.
+    pub conductivity: f32,
+    /// Divides the temperature change of exchanged heat.
+    pub heat_capacity: f32,
+    pub burnable: Option<Burnable>,
+    /// A burning particle color is picked from these when it ignites.
+    pub burn_colors: Vec<Color>,
+    /// Whether the particle is a flame, which feeds on burning particles and needs air.
+    pub flame: bool,
+    /// Whether the material is air to fire, like the empty cells.
+    pub oxidizer: bool,
+    /// Whether the material puts out the flames and burning particles it touches.
+    pub extinguishes: bool,
+    /// Checked in order against the neighbors, every tick.
+    pub reactions: Vec<Reaction>,
+    pub packing: Option<Packing>,
+    pub electric: Option<Electric>,
+    pub sprout: Option<Sprout>,
+    pub growth: Option<Growth>,
+    pub solution: Option<Solution>,
+    pub soluble: Option<Soluble>,
+    pub emitter: Option<Emitter>,
+    /// Whether the material removes the particles touching it, but for drains and emitters.
+    pub drain: bool,
+    /// A solid bursts when a sealed gas pocket next to it is filled past this share.
+    pub burst_pressure: Option<f32>,
+    /// Strength of a blast, from 1 at its center to 0 at its edge, the particle withstands.
+    pub blast_resistance: f32,
+    pub fan: Option<Fan>,
+}
+
+/// Properties of every material, indexed by [`MaterialId`].
+pub struct MaterialTable {
+    materials: Vec<MaterialProperties>,
+}
+
+/// Materials shipped with the crate, used until the materials asset is loaded.
+const DEFAULT_MATERIALS: &str = include_str!("../../assets/sandbox.materials.ron");
+
+impl Default for MaterialTable {
+    fn default() -> Self {
+        let asset = MaterialsAsset::from_bytes(DEFAULT_MATERIALS.as_bytes())
+            .expect("Default materials should be valid");
+        MaterialTable::from_asset(&asset).expect("Default materials should be valid")
+    }
+}
+
+impl MaterialTable {
+    pub fn new(materials: Vec<MaterialProperties>) -> Self {
+        Self { materials }
+    }
+
+    #[inline]
+    pub fn get(&self, material: MaterialId) -> &MaterialProperties {
+        &self.materials[material.0 as usize]
+    }
+
+    /// Finds a material by its name.
+    pub fn id(&self, name: &str) -> Option<MaterialId> {
+        self.materials
+            .iter()
+            .position(|properties| properties.name == name)
+            .map(|index| MaterialId(index as u16))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (MaterialId, &MaterialProperties)> {
+        self.materials
+            .iter()
+            .enumerate()
+            .map(|(index, properties)| (MaterialId(index as u16), properties))
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.materials.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.materials.is_empty()
+    }
+
+    /// Creates a particle of the material. Materials without `Temperature` start at
+    /// `ambient_temperature`.
+    pub fn get_particle(
+        &self,
+        material: MaterialId,
+        ambient_temperature: i32,
+        rng: &mut impl Rng,
+    ) -> Particle {
+        let properties = self.get(material);
+
+        let mut health = properties.health;
+        if properties.health_variation > 0 {
+            health += rng.gen_range(0..properties.health_variation);
+        }
+
+        // Particle spread on spawm
+        let velocity = match properties.spawn_velocity {
+            Some(spawn_velocity) => Velocity::new(
+                rng.gen_range(spawn_velocity.x.0..=spawn_velocity.x.1),
+                rng.gen_range(spawn_velocity.y.0..=spawn_velocity.y.1),
+            ),
+            None => Velocity::default(),
+        };
+
+        Particle {
+            material,
+            color: self.color(material, rng),
+            velocity,
+            health,
+            temperature: properties
+                .temperature
+                .map_or(ambient_temperature, |temperature| {
+                    temperature.start_temperature
+                }),
+            burning: false,
+            // Placed solutions start half saturated
+            counter: properties
+                .solution
+                .map_or(0, |solution| solution.capacity / 2),
+            updated: false,
+        }
+    }
+
+    /// Picks the color of a new particle of the material.
+    pub fn color(&self, material: MaterialId, rng: &mut impl Rng) -> (u8, u8, u8, u8) {
+        let properties = self.get(material);
+        pick_color(&properties.colors, properties.color_variation, rng)
+    }
+
+    /// Picks the color of a burning particle of the material.
+    pub fn burn_color(&self, material: MaterialId, rng: &mut impl Rng) -> (u8, u8, u8, u8) {
+        let properties = self.get(material);
+        pick_color(&properties.burn_colors, properties.color_variation, rng)
+    }
+}
+
+fn pick_color(colors: &[Color], range: f32, rng: &mut impl Rng) -> (u8, u8, u8, u8) {
+    let mut c: Color = match colors.len() {
+        0 => Color::WHITE,
+        1 => colors[0],
+        len => colors[rng.gen_range(0..len)],
+    };
+    if range > 0.0 {
+        c.set_l(c.l() + rng.gen_range(-range..=range));
+    }
+    (
+        (c.r() * 255.0) as u8,
+        (c.g() * 255.0) as u8,
+        (c.b() * 255.0) as u8,
+        (c.a() * 255.0) as u8,
+    )
+}
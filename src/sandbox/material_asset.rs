@@ -0,0 +1,676 @@
+use bevy::asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext};
+use bevy::prelude::*;
+use bevy::utils::BoxedFuture;
+use serde::Deserialize;
+use std::fmt;
+use std::sync::Arc;
+
+use super::material::{MaterialProperties, MaterialTable};
+use super::particle::*;
+use super::{Sandbox, SandboxSettings};
+
+/// Materials described in a `.materials.ron` file.
+#[derive(Asset, TypePath, Deserialize)]
+pub struct MaterialsAsset {
+    pub materials: Vec<MaterialDefinition>,
+}
+
+#[derive(Deserialize)]
+pub struct MaterialDefinition {
+    pub name: String,
+    /// Hex colors, `#rrggbb` or `#rrggbbaa`.
+    pub colors: Vec<String>,
+    #[serde(default)]
+    pub color_variation: f32,
+    #[serde(default = "default_true")]
+    pub selectable: bool,
+    #[serde(default = "default_health")]
+    pub health: i32,
+    #[serde(default)]
+    pub health_variation: i32,
+    #[serde(default)]
+    pub lifespan: Option<ReplacementDefinition>,
+    pub movement_type: MovementType,
+    /// Particles can only displace particles with a lower density.
+    #[serde(default = "default_density")]
+    pub density: u32,
+    #[serde(default)]
+    pub spread_rate: i32,
+    #[serde(default)]
+    pub viscosity: f32,
+    #[serde(default)]
+    pub surface_tension: f32,
+    #[serde(default = "default_true")]
+    pub use_gravity: bool,
+    #[serde(default)]
+    pub drag: f32,
+    #[serde(default)]
+    pub rigid: bool,
+    #[serde(default)]
+    pub anchored: bool,
+    #[serde(default)]
+    pub crumble: Option<String>,
+    #[serde(default)]
+    pub spawn_velocity: Option<SpawnVelocity>,
+    #[serde(default)]
+    pub acidity: Option<i32>,
+    #[serde(default)]
+    pub neutralized: Option<ReplacementDefinition>,
+    #[serde(default)]
+    pub corrodable: bool,
+    #[serde(default)]
+    pub corrosion_resistance: f32,
+    #[serde(default)]
+    pub corrosion_residue: Option<ReplacementDefinition>,
+    #[serde(default)]
+    pub temperature: Option<TemperatureDefinition>,
+    #[serde(default)]
+    pub temperature_changer: Option<i32>,
+    #[serde(default)]
+    pub conductivity: f32,
+    #[serde(default = "default_heat_capacity")]
+    pub heat_capacity: f32,
+    #[serde(default)]
+    pub burnable: Option<BurnableDefinition>,
+    #[serde(default)]
+    pub flame: bool,
+    #[serde(default)]
+    pub oxidizer: bool,
+    #[serde(default)]
+    pub extinguishes: bool,
+    #[serde(default)]
+    pub reactions: Vec<ReactionDefinition>,
+    #[serde(default)]
+    pub packing: Option<PackingDefinition>,
+    #[serde(default)]
+    pub burst_pressure: Option<f32>,
+    #[serde(default)]
+    pub blast_resistance: f32,
+    #[serde(default)]
+    pub electric: Option<Electric>,
+    #[serde(default)]
+    pub sprout: Option<SproutDefinition>,
+    #[serde(default)]
+    pub growth: Option<GrowthDefinition>,
+    #[serde(default)]
+    pub solution: Option<SolutionDefinition>,
+    #[serde(default)]
+    pub soluble: Option<SolubleDefinition>,
+    #[serde(default)]
+    pub emitter: Option<EmitterDefinition>,
+    #[serde(default)]
+    pub drain: bool,
+    #[serde(default)]
+    pub fan: Option<Fan>,
+}
+
+#[derive(Deserialize)]
+pub struct ReplacementDefinition {
+    pub material: Option<String>,
+    pub probability: f32,
+}
+
+#[derive(Deserialize)]
+pub struct TemperatureDefinition {
+    pub start: i32,
+    pub coolable: bool,
+    pub heatable: bool,
+    #[serde(default = "default_hysteresis")]
+    pub hysteresis: i32,
+    #[serde(default)]
+    pub melting: Option<TransitionDefinition>,
+    #[serde(default)]
+    pub boiling: Option<TransitionDefinition>,
+    #[serde(default)]
+    pub freezing: Option<TransitionDefinition>,
+    #[serde(default)]
+    pub condensation: Option<TransitionDefinition>,
+    #[serde(default)]
+    pub explosion: Option<ExplosionDefinition>,
+}
+
+#[derive(Deserialize)]
+pub struct TransitionDefinition {
+    pub temperature: i32,
+    pub material: Option<String>,
+    #[serde(default = "default_probability")]
+    pub probability: f32,
+}
+
+#[derive(Deserialize)]
+pub struct ExplosionDefinition {
+    pub temperature: i32,
+    pub radius: i32,
+    #[serde(default)]
+    pub heat: i32,
+    #[serde(default)]
+    pub debris: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct BurnableDefinition {
+    pub burn_temperature: i32,
+    pub burn_ticks: i32,
+    pub burn_colors: Vec<String>,
+    #[serde(default)]
+    pub flammability: f32,
+    #[serde(default)]
+    pub emission: Option<String>,
+    #[serde(default)]
+    pub smoke: Option<String>,
+    #[serde(default)]
+    pub residue: Option<ReplacementDefinition>,
+}
+
+#[derive(Deserialize)]
+pub struct ReactionDefinition {
+    pub with: String,
+    pub product: Option<String>,
+    pub neighbor_product: Option<String>,
+    #[serde(default = "default_probability")]
+    pub probability: f32,
+    #[serde(default)]
+    pub temperature: Option<(i32, i32)>,
+}
+
+#[derive(Deserialize)]
+pub struct PackingDefinition {
+    pub depth: usize,
+    pub material: String,
+    #[serde(default = "default_probability")]
+    pub probability: f32,
+}
+
+#[derive(Deserialize)]
+pub struct SproutDefinition {
+    pub material: String,
+    pub soil: Vec<String>,
+    pub water: String,
+    #[serde(default = "default_probability")]
+    pub probability: f32,
+}
+
+#[derive(Deserialize)]
+pub struct GrowthDefinition {
+    pub material: String,
+    pub rate: f32,
+    #[serde(default)]
+    pub water: Option<String>,
+    #[serde(default)]
+    pub capacity: i32,
+    #[serde(default)]
+    pub bloom: Option<ReplacementDefinition>,
+}
+
+#[derive(Deserialize)]
+pub struct SolutionDefinition {
+    pub solvent: String,
+    pub solute: String,
+    pub capacity: i32,
+    #[serde(default)]
+    pub density: u32,
+    #[serde(default)]
+    pub freezing_drop: i32,
+    #[serde(default)]
+    pub conductive: i32,
+    #[serde(default = "default_probability")]
+    pub diffusion: f32,
+}
+
+#[derive(Deserialize)]
+pub struct SolubleDefinition {
+    pub solution: String,
+    #[serde(default = "default_probability")]
+    pub probability: f32,
+}
+
+#[derive(Deserialize)]
+pub struct EmitterDefinition {
+    #[serde(default)]
+    pub material: Option<String>,
+    #[serde(default = "default_probability")]
+    pub rate: f32,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_health() -> i32 {
+    50
+}
+
+fn default_density() -> u32 {
+    u32::MAX
+}
+
+fn default_hysteresis() -> i32 {
+    5
+}
+
+fn default_heat_capacity() -> f32 {
+    1.0
+}
+
+fn default_probability() -> f32 {
+    1.0
+}
+
+#[derive(Debug)]
+pub enum MaterialsError {
+    Io(std::io::Error),
+    Ron(ron::error::SpannedError),
+    UnknownMaterial(String),
+    /// Materials are referred to by name, so it has to be unique.
+    DuplicateMaterial(String),
+    /// Particles keep a single counter, for the growth, the fuse of an explosion, the solute of
+    /// a solution or the material copied by a clone block, so a material can't have two of them.
+    ConflictingCounters(String),
+    InvalidColor(String),
+}
+
+impl fmt::Display for MaterialsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MaterialsError::Io(err) => write!(f, "Could not read materials: {err}"),
+            MaterialsError::Ron(err) => write!(f, "Could not parse materials: {err}"),
+            MaterialsError::UnknownMaterial(name) => write!(f, "Unknown material `{name}`"),
+            MaterialsError::DuplicateMaterial(name) => {
+                write!(f, "Material `{name}` is defined more than once")
+            }
+            MaterialsError::ConflictingCounters(name) => write!(
+                f,
+                "Material `{name}` has more than one of growth, explosion, solution and clone emitter"
+            ),
+            MaterialsError::InvalidColor(color) => write!(f, "Invalid color `{color}`"),
+        }
+    }
+}
+
+impl std::error::Error for MaterialsError {}
+
+impl MaterialsAsset {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MaterialsError> {
+        ron::de::from_bytes(bytes).map_err(MaterialsError::Ron)
+    }
+}
+
+impl MaterialTable {
+    /// Builds the table from material definitions, resolving materials referenced by name.
+    pub fn from_asset(asset: &MaterialsAsset) -> Result<Self, MaterialsError> {
+        for (index, definition) in asset.materials.iter().enumerate() {
+            if asset.materials[..index]
+                .iter()
+                .any(|other| other.name == definition.name)
+            {
+                return Err(MaterialsError::DuplicateMaterial(definition.name.clone()));
+            }
+
+            let counters = [
+                definition.growth.is_some(),
+                definition
+                    .temperature
+                    .as_ref()
+                    .is_some_and(|temperature| temperature.explosion.is_some()),
+                definition.solution.is_some(),
+                definition
+                    .emitter
+                    .as_ref()
+                    .is_some_and(|emitter| emitter.material.is_none()),
+            ];
+            if counters.into_iter().filter(|&counter| counter).count() > 1 {
+                return Err(MaterialsError::ConflictingCounters(definition.name.clone()));
+            }
+        }
+
+        let find = |name: &str| -> Result<MaterialId, MaterialsError> {
+            match asset.materials.iter().position(|m| m.name == name) {
+                Some(index) => Ok(MaterialId(index as u16)),
+                None => Err(MaterialsError::UnknownMaterial(name.to_string())),
+            }
+        };
+        let resolve = |name: &Option<String>| -> Result<Option<MaterialId>, MaterialsError> {
+            name.as_deref().map(find).transpose()
+        };
+        let replacement = |definition: &ReplacementDefinition| {
+            Ok::<_, MaterialsError>(ParticleReplacement::new(
+                resolve(&definition.material)?,
+                definition.probability,
+            ))
+        };
+        let transition = |definition: &Option<TransitionDefinition>| match definition {
+            Some(definition) => Ok::<_, MaterialsError>(Some(PhaseTransition {
+                temperature: definition.temperature,
+                replacement: ParticleReplacement::new(
+                    resolve(&definition.material)?,
+                    definition.probability,
+                ),
+            })),
+            None => Ok(None),
+        };
+
+        let mut materials = Vec::with_capacity(asset.materials.len());
+        for definition in asset.materials.iter() {
+            let temperature = match &definition.temperature {
+                Some(temperature) => Some(Temperature {
+                    start_temperature: temperature.start,
+                    coolable: temperature.coolable,
+                    heatable: temperature.heatable,
+                    hysteresis: temperature.hysteresis,
+                    melting: transition(&temperature.melting)?,
+                    boiling: transition(&temperature.boiling)?,
+                    freezing: transition(&temperature.freezing)?,
+                    condensation: transition(&temperature.condensation)?,
+                    explosion: match &temperature.explosion {
+                        Some(explosion) => Some(Explosion {
+                            temperature: explosion.temperature,
+                            radius: explosion.radius,
+                            heat: explosion.heat,
+                            debris: resolve(&explosion.debris)?,
+                        }),
+                        None => None,
+                    },
+                }),
+                None => None,
+            };
+
+            let (burnable, burn_colors) = match &definition.burnable {
+                Some(burnable) => (
+                    Some(Burnable {
+                        burn_temperature: burnable.burn_temperature,
+                        burn_ticks: burnable.burn_ticks,
+                        flammability: burnable.flammability,
+                        emission: resolve(&burnable.emission)?,
+                        smoke: resolve(&burnable.smoke)?,
+                        residue: match &burnable.residue {
+                            Some(residue) => replacement(residue)?,
+                            None => ParticleReplacement::new(None, 1.0),
+                        },
+                    }),
+                    parse_colors(&burnable.burn_colors)?,
+                ),
+                None => (None, Vec::new()),
+            };
+
+            let mut reactions = Vec::with_capacity(definition.reactions.len());
+            for reaction in definition.reactions.iter() {
+                reactions.push(Reaction {
+                    with: find(&reaction.with)?,
+                    product: resolve(&reaction.product)?,
+                    neighbor_product: resolve(&reaction.neighbor_product)?,
+                    probability: reaction.probability,
+                    temperature: reaction.temperature,
+                });
+            }
+
+            materials.push(MaterialProperties {
+                name: definition.name.clone(),
+                selectable: definition.selectable,
+                colors: parse_colors(&definition.colors)?,
+                color_variation: definition.color_variation,
+                health: definition.health,
+                health_variation: definition.health_variation,
+                lifespan: match &definition.lifespan {
+                    Some(lifespan) => Some(LifeSpan::new(replacement(lifespan)?)),
+                    None => None,
+                },
+                density: Density(definition.density),
+                movement_type: definition.movement_type,
+                spread_rate: definition.spread_rate,
+                viscosity: definition.viscosity,
+                surface_tension: definition.surface_tension,
+                use_gravity: definition.use_gravity,
+                drag: definition.drag,
+                rigid: definition.rigid,
+                anchored: definition.anchored,
+                crumble: resolve(&definition.crumble)?,
+                spawn_velocity: definition.spawn_velocity,
+                acidity: definition.acidity.map(Acidity),
+                neutralized: match &definition.neutralized {
+                    Some(neutralized) => Some(replacement(neutralized)?),
+                    None => None,
+                },
+                corrodable: definition.corrodable,
+                corrosion_resistance: definition.corrosion_resistance,
+                corrosion_residue: match &definition.corrosion_residue {
+                    Some(residue) => Some(replacement(residue)?),
+                    None => None,
+                },
+                temperature,
+                temperature_changer: definition.temperature_changer.map(TemperatureChanger),
+                conductivity: definition.conductivity,
+                heat_capacity: definition.heat_capacity,
+                burnable,
+                burn_colors,
+                flame: definition.flame,
+                oxidizer: definition.oxidizer,
+                extinguishes: definition.extinguishes,
+                reactions,
+                packing: match &definition.packing {
+                    Some(packing) => Some(Packing {
+                        depth: packing.depth,
+                        material: find(&packing.material)?,
+                        probability: packing.probability,
+                    }),
+                    None => None,
+                },
+                burst_pressure: definition.burst_pressure,
+                blast_resistance: definition.blast_resistance,
+                electric: definition.electric,
+                sprout: match &definition.sprout {
+                    Some(sprout) => Some(Sprout {
+                        material: find(&sprout.material)?,
+                        soil: sprout
+                            .soil
+                            .iter()
+                            .map(|soil| find(soil))
+                            .collect::<Result<_, _>>()?,
+                        water: find(&sprout.water)?,
+                        probability: sprout.probability,
+                    }),
+                    None => None,
+                },
+                growth: match &definition.growth {
+                    Some(growth) => Some(Growth {
+                        material: find(&growth.material)?,
+                        rate: growth.rate,
+                        water: resolve(&growth.water)?,
+                        capacity: growth.capacity,
+                        bloom: match &growth.bloom {
+                            Some(bloom) => Some(replacement(bloom)?),
+                            None => None,
+                        },
+                    }),
+                    None => None,
+                },
+                solution: match &definition.solution {
+                    Some(solution) => Some(Solution {
+                        solvent: find(&solution.solvent)?,
+                        solute: find(&solution.solute)?,
+                        capacity: solution.capacity.max(1),
+                        density: solution.density,
+                        freezing_drop: solution.freezing_drop,
+                        conductive: solution.conductive,
+                        diffusion: solution.diffusion,
+                    }),
+                    None => None,
+                },
+                soluble: match &definition.soluble {
+                    Some(soluble) => Some(Soluble {
+                        solution: find(&soluble.solution)?,
+                        probability: soluble.probability,
+                    }),
+                    None => None,
+                },
+                emitter: match &definition.emitter {
+                    Some(emitter) => Some(Emitter {
+                        material: resolve(&emitter.material)?,
+                        rate: emitter.rate,
+                    }),
+                    None => None,
+                },
+                drain: definition.drain,
+                fan: definition.fan,
+            });
+        }
+
+        Ok(MaterialTable::new(materials))
+    }
+}
+
+fn parse_colors(colors: &[String]) -> Result<Vec<Color>, MaterialsError> {
+    colors
+        .iter()
+        .map(|color| Color::hex(color).map_err(|_| MaterialsError::InvalidColor(color.clone())))
+        .collect()
+}
+
+#[derive(Default)]
+pub struct MaterialsLoader;
+
+impl AssetLoader for MaterialsLoader {
+    type Asset = MaterialsAsset;
+    type Settings = ();
+    type Error = MaterialsError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<MaterialsAsset, MaterialsError>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader
+                .read_to_end(&mut bytes)
+                .await
+                .map_err(MaterialsError::Io)?;
+            MaterialsAsset::from_bytes(&bytes)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["materials.ron"]
+    }
+}
+
+#[derive(Resource)]
+pub struct MaterialsHandle(pub Handle<MaterialsAsset>);
+
+pub fn load_materials(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    settings: Res<SandboxSettings>,
+) {
+    let handle = asset_server.load(settings.materials_path.clone());
+    commands.insert_resource(MaterialsHandle(handle));
+}
+
+/// Replaces the materials of the sandbox once the materials asset is loaded, and again
+/// every time it is reloaded.
+pub fn apply_materials(
+    mut events: EventReader<AssetEvent<MaterialsAsset>>,
+    assets: Res<Assets<MaterialsAsset>>,
+    handle: Res<MaterialsHandle>,
+    mut sandbox_query: Query<&mut Sandbox>,
+) {
+    for event in events.read() {
+        if !event.is_loaded_with_dependencies(&handle.0) {
+            continue;
+        }
+
+        let asset = match assets.get(&handle.0) {
+            Some(asset) => asset,
+            None => continue,
+        };
+
+        match MaterialTable::from_asset(asset) {
+            Ok(materials) => {
+                info!("Loaded {0} materials", materials.len());
+                let materials = Arc::new(materials);
+                for mut sandbox in sandbox_query.iter_mut() {
+                    sandbox.set_materials(materials.clone());
+                }
+            }
+            Err(err) => error!("{err}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(materials: &str) -> Result<MaterialTable, MaterialsError> {
+        let asset = MaterialsAsset::from_bytes(format!("(materials: [{materials}])").as_bytes())?;
+        MaterialTable::from_asset(&asset)
+    }
+
+    #[test]
+    fn shipped_materials_are_valid() {
+        let asset =
+            MaterialsAsset::from_bytes(include_bytes!("../../assets/sandbox.materials.ron"))
+                .unwrap();
+        let materials = MaterialTable::from_asset(&asset).unwrap();
+
+        assert_eq!(materials.len(), asset.materials.len());
+        let water = materials.id("Water").unwrap();
+        assert_eq!(materials.get(water).name, "Water");
+    }
+
+    #[test]
+    fn unknown_reaction_material() {
+        let result = table(
+            r#"(name: "Acid", colors: [], movement_type: Liquid, reactions: [
+                (with: "Lye", product: None, neighbor_product: None),
+            ])"#,
+        );
+        assert!(matches!(result, Err(MaterialsError::UnknownMaterial(name)) if name == "Lye"));
+    }
+
+    #[test]
+    fn unknown_smoke_material() {
+        let result = table(
+            r#"(name: "Wood", colors: [], movement_type: Solid, burnable: Some((
+                burn_temperature: 100, burn_ticks: 10, burn_colors: [], smoke: Some("Soot"),
+            )))"#,
+        );
+        assert!(matches!(result, Err(MaterialsError::UnknownMaterial(name)) if name == "Soot"));
+    }
+
+    #[test]
+    fn unknown_debris_material() {
+        let result = table(
+            r#"(name: "TNT", colors: [], movement_type: Solid, temperature: Some((
+                start: 20, coolable: true, heatable: true,
+                explosion: Some((temperature: 200, radius: 4, debris: Some("Rubble"))),
+            )))"#,
+        );
+        assert!(matches!(result, Err(MaterialsError::UnknownMaterial(name)) if name == "Rubble"));
+    }
+
+    #[test]
+    fn duplicate_material() {
+        let result = table(
+            r#"(name: "Sand", colors: [], movement_type: Powder),
+            (name: "Sand", colors: [], movement_type: Liquid)"#,
+        );
+        assert!(matches!(result, Err(MaterialsError::DuplicateMaterial(name)) if name == "Sand"));
+    }
+
+    #[test]
+    fn conflicting_counters() {
+        let result = table(
+            r#"(name: "Nitro", colors: [], movement_type: Liquid,
+                temperature: Some((
+                    start: 20, coolable: true, heatable: true,
+                    explosion: Some((temperature: 200, radius: 4)),
+                )),
+                solution: Some((solvent: "Nitro", solute: "Nitro", capacity: 4, density: 10)),
+            )"#,
+        );
+        assert!(
+            matches!(result, Err(MaterialsError::ConflictingCounters(name)) if name == "Nitro")
+        );
+    }
+}
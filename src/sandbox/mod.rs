@@ -1,74 +1,131 @@
 use bevy::prelude::*;
-use bevy::render::{render_resource::*, texture::ImageSampler};
-use bevy::time::common_conditions::on_timer;
-use std::f32::consts::PI;
-use std::time::Duration;
 
-use crate::RESOLUTION;
-
-use self::interaction::{InterationPlugin, PANEL_HEIGHT};
-use self::render::render_particles;
-use self::sandbox::Sandbox;
+use self::clock::advance_clock;
+use self::material_asset::{apply_materials, load_materials, MaterialsAsset, MaterialsLoader};
 use self::simulation::update_particles;
 
-mod interaction;
+pub mod chunk;
+pub mod clock;
+mod electricity;
+mod emitter;
+mod fire;
+mod growth;
+mod marks;
+pub mod material;
+pub mod material_asset;
 mod movement;
 pub mod particle;
+mod pressure;
+mod reaction;
+#[cfg(feature = "render")]
 mod render;
+mod rigid_body;
+#[allow(clippy::module_inception)]
 mod sandbox;
-mod simulation;
+pub mod simulation;
+mod solution;
 mod temperature;
+mod wind;
+
+pub use self::clock::SimulationClock;
+pub use self::material::{MaterialProperties, MaterialTable};
+pub use self::particle::{MaterialId, Particle};
+#[cfg(feature = "render")]
+pub use self::render::SandboxRenderPlugin;
+pub use self::sandbox::{Sandbox, DEFAULT_AMBIENT_TEMPERATURE};
+pub use self::simulation::{step_sandbox, UpdateMode};
+
+/// Settings used to build the sandbox when the plugin starts.
+#[derive(Resource, Clone, Debug)]
+pub struct SandboxSettings {
+    /// Size of the area covered by the sandbox, in world units.
+    pub resolution: Vec2,
+    /// Size of a single cell, in world units.
+    pub cell_size: f32,
+    /// Position of the center of the sandbox, in world units.
+    pub translation: Vec2,
+    pub update_mode: UpdateMode,
+    /// Number of simulation ticks per second, at normal speed.
+    pub tick_rate: f32,
+    /// Seed of the sandbox random generator. Random by default, and logged on startup
+    /// so a run can be reproduced.
+    pub seed: u64,
+    /// Path of the materials asset, relative to the assets folder. The materials shipped
+    /// with the crate are used until it is loaded.
+    pub materials_path: String,
+    /// Temperature of the empty cells, particles exposed to them relax toward it.
+    pub ambient_temperature: i32,
+    /// Wind blowing where nothing else stirs the air, in cells per tick.
+    pub ambient_wind: Vec2,
+}
+
+impl Default for SandboxSettings {
+    fn default() -> Self {
+        Self {
+            resolution: Vec2::new(1280.0, 960.0),
+            cell_size: 4.0,
+            translation: Vec2::ZERO,
+            update_mode: UpdateMode::default(),
+            tick_rate: 60.0,
+            seed: rand::random(),
+            materials_path: "sandbox.materials.ron".to_string(),
+            ambient_temperature: DEFAULT_AMBIENT_TEMPERATURE,
+            ambient_wind: Vec2::ZERO,
+        }
+    }
+}
 
-pub const CELL_SIZE: f32 = 4.0;
-pub const SANDBOX_SIZE: (f32, f32) = (
-    RESOLUTION.0 / CELL_SIZE,
-    (RESOLUTION.1 - PANEL_HEIGHT) / CELL_SIZE,
-);
+impl SandboxSettings {
+    /// Number of cells of the sandbox, horizontally and vertically.
+    pub fn size(&self) -> (usize, usize) {
+        (
+            (self.resolution.x / self.cell_size) as usize,
+            (self.resolution.y / self.cell_size) as usize,
+        )
+    }
+
+    /// Converts a world position to the coordinates of the cell under it.
+    pub fn world_to_cell(&self, world_pos: Vec2) -> Option<(usize, usize)> {
+        let (width, height) = self.size();
+        let cell = (world_pos - self.translation) / self.cell_size
+            + Vec2::new(width as f32, height as f32) * 0.5;
 
-pub struct SandboxPlugin;
+        if cell.x < 0.0 || cell.y < 0.0 || cell.x >= width as f32 || cell.y >= height as f32 {
+            return None;
+        }
+        Some((cell.x as usize, cell.y as usize))
+    }
+}
+
+/// Simulates the sandbox, without rendering it (see `SandboxRenderPlugin`), so it also runs
+/// in headless apps.
+#[derive(Default)]
+pub struct SandboxPlugin {
+    pub settings: SandboxSettings,
+}
 
 impl Plugin for SandboxPlugin {
     fn build(&self, app: &mut App) {
-        info!("Sandbox size {0} {1}", SANDBOX_SIZE.0, SANDBOX_SIZE.1);
-        app.add_plugins(InterationPlugin)
-            .add_systems(Startup, spawn_sandbox)
+        let (width, height) = self.settings.size();
+        info!("Sandbox size {0} {1}", width, height);
+        info!("Sandbox seed {0}", self.settings.seed);
+        app.insert_resource(self.settings.clone())
+            .insert_resource(self.settings.update_mode)
+            .insert_resource(SimulationClock::new(self.settings.tick_rate))
+            .init_asset::<MaterialsAsset>()
+            .register_asset_loader(MaterialsLoader)
+            .add_systems(Startup, (spawn_sandbox, load_materials))
             .add_systems(
                 Update,
-                (update_particles, render_particles)
-                    .chain()
-                    .distributive_run_if(on_timer(Duration::from_secs_f32(1. / 60.))),
+                (advance_clock, apply_materials, update_particles).chain(),
             );
     }
 }
 
-pub fn spawn_sandbox(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
-    let image_handle = {
-        let mut image = Image::new_fill(
-            Extent3d {
-                width: SANDBOX_SIZE.0 as u32,
-                height: SANDBOX_SIZE.1 as u32,
-                depth_or_array_layers: 1,
-            },
-            TextureDimension::D2,
-            &[0, 0, 0, 0],
-            TextureFormat::Rgba8UnormSrgb,
-        );
-        image.sampler = ImageSampler::nearest();
-        images.add(image)
-    };
-
-    commands
-        .spawn(Sandbox::new(
-            SANDBOX_SIZE.0 as usize,
-            SANDBOX_SIZE.1 as usize,
-        ))
-        .insert(SpriteBundle {
-            texture: image_handle,
-            transform: Transform {
-                translation: Vec3::new(0.0, PANEL_HEIGHT * 0.5, 1.0),
-                scale: Vec3::new(CELL_SIZE, CELL_SIZE, 1.0),
-                rotation: Quat::from_euler(EulerRot::XYZ, 0.0, PI, PI),
-            },
-            ..Default::default()
-        });
+pub fn spawn_sandbox(mut commands: Commands, settings: Res<SandboxSettings>) {
+    let (width, height) = settings.size();
+    let mut sandbox = Sandbox::new(width, height, settings.seed);
+    sandbox.set_ambient_temperature(settings.ambient_temperature);
+    sandbox.set_ambient_wind(settings.ambient_wind);
+    commands.spawn(sandbox);
 }
@@ -1,10 +1,11 @@
 use bevy::prelude::*;
-use rand::*;
+use rand::Rng;
 
 use crate::sandbox::particle::*;
 use crate::utils::*;
 
 use super::sandbox::Sandbox;
+use super::temperature::round_randomly;
 
 #[derive(Default)]
 struct StepData {
@@ -18,23 +19,41 @@ struct StepData {
 }
 
 pub fn step_movement(x: usize, y: usize, sandbox: &mut Sandbox) {
+    // Rigid bodies move as a whole, see `update_rigid_bodies`
+    if sandbox
+        .properties(sandbox.get(x, y).unwrap().material)
+        .rigid
+    {
+        return;
+    }
+
     apply_gravity(x, y, sandbox);
 
-    let step_data = get_step_data(x as i32, y as i32, sandbox);
+    let properties = sandbox.properties(sandbox.get(x, y).unwrap().material);
+    let (viscosity, surface_tension) = (properties.viscosity, properties.surface_tension);
+
+    let clockwise_priority = sandbox.rng().gen_bool(0.5);
+    let sideways = !sandbox.rng().gen_bool(viscosity as f64);
+    let tension = sandbox.rng().gen_bool(surface_tension as f64);
+    let step_data = get_step_data(
+        x as i32,
+        y as i32,
+        clockwise_priority,
+        sideways,
+        tension,
+        sandbox,
+    );
 
     let (new_x, new_y) = (step_data.new_x as usize, step_data.new_y as usize);
 
     if step_data.swap {
-        let current_particle = sandbox.get(x, y).unwrap();
         let (other_x, other_y) = (step_data.other_x as usize, step_data.other_y as usize);
 
-        sandbox.set(new_x, new_y, Some(*current_particle));
-
-        if new_x != x && new_y != y {
-            sandbox.set(x, y, None);
-        }
-
+        // The particle takes the cell of the one it displaces, which takes the last free cell
+        // on the way. Both are done for this tick, so the displaced one doesn't swap back.
+        sandbox.swap(x, y, new_x, new_y);
         sandbox.swap(new_x, new_y, other_x, other_y);
+        sandbox.mark_updated(new_x, new_y);
         sandbox.mark_updated(other_x, other_y);
         return;
     }
@@ -48,54 +67,91 @@ pub fn step_movement(x: usize, y: usize, sandbox: &mut Sandbox) {
 }
 
 pub fn apply_gravity(x: usize, y: usize, sandbox: &mut Sandbox) {
-    let particle = sandbox.get_mut(x, y).unwrap();
+    let material = sandbox.get(x, y).unwrap().material;
+    let properties = sandbox.properties(material);
+    let (use_gravity, movement_type, drag) = (
+        properties.use_gravity,
+        properties.movement_type,
+        properties.drag,
+    );
 
-    if !particle.use_gravity {
+    if !use_gravity {
         return;
     }
 
-    match particle.movement_type {
+    let particle = sandbox.get_mut(x, y).unwrap();
+    match movement_type {
         MovementType::Powder | MovementType::Liquid => particle.velocity.y -= 1,
         MovementType::Gas => particle.velocity.y += 1,
         MovementType::Solid => return,
     }
-    particle.velocity.zero_out_x();
+    if drag <= 0.0 {
+        particle.velocity.zero_out_x();
+        return;
+    }
+
+    // Light particles are carried along by the wind, which also slows down their fall or rise
+    let velocity = particle.velocity;
+    let wind = sandbox.wind(x, y);
+    let change_x = round_randomly((wind.x - velocity.x as f32) * drag, sandbox);
+    let change_y = round_randomly((wind.y - velocity.y as f32) * drag, sandbox);
+    let particle = sandbox.get_mut(x, y).unwrap();
+    particle.velocity.x += change_x;
+    particle.velocity.y += change_y;
 }
 
-fn get_step_data(x: i32, y: i32, sandbox: &Sandbox) -> StepData {
+/// `sideways` is whether a liquid or gas may move sideways this tick, as rolled from its
+/// viscosity, and `tension` whether its surface tension holds it back from forming a film.
+fn get_step_data(
+    x: i32,
+    y: i32,
+    clockwise_priority: bool,
+    sideways: bool,
+    tension: bool,
+    sandbox: &Sandbox,
+) -> StepData {
     let particle = sandbox.get(x as usize, y as usize).unwrap();
+    let properties = sandbox.properties(particle.material);
 
-    let rotation_type_amount = match particle.movement_type {
+    let rotation_type_amount = match properties.movement_type {
         MovementType::Powder => 3,
         MovementType::Liquid | MovementType::Gas => 5,
         MovementType::Solid => return StepData::default(),
     };
 
-    let clockwise_priority = thread_rng().gen_bool(0.5);
     let movement_rotations = match clockwise_priority {
         true => vec![0, 1, 2, 3, 4],
         false => vec![0, 2, 1, 4, 3],
     };
 
     let valid_rotations = movement_rotations.iter().take(rotation_type_amount);
-    let spread_rate = particle.spread_rate;
+    let spread_rate = properties.spread_rate;
+    // Viscous materials spread less far sideways
+    let sideways_spread = (spread_rate as f32 * (1.0 - properties.viscosity)).round() as i32;
     for &i in valid_rotations {
+        if i >= 1 && !sideways {
+            break;
+        }
+
         let mut step_data = line_with_rotation(
             x,
             y,
             particle.velocity.x,
             particle.velocity.y,
-            spread_rate,
+            if i == 0 { spread_rate } else { sideways_spread },
             sandbox,
             i,
         );
 
         if step_data.moved {
+            if i >= 3 && tension && is_film(&step_data, particle.material, sandbox) {
+                continue;
+            }
             return step_data;
         }
 
         if let Some(entity) = step_data.other_particle {
-            if particle.density.0 > entity.density.0 {
+            if displaces(particle, &entity, sandbox) {
                 step_data.swap = true;
                 return step_data;
             }
@@ -105,6 +161,46 @@ fn get_step_data(x: i32, y: i32, sandbox: &Sandbox) -> StepData {
     StepData::default()
 }
 
+/// Whether a particle of `mover` pushes its way through a particle of `blocker`: powders and
+/// liquids sink into lighter powders and fluids, gases rise through heavier fluids.
+fn displaces(mover: &Particle, blocker: &Particle, sandbox: &Sandbox) -> bool {
+    let (mover_properties, blocker_properties) = (
+        sandbox.properties(mover.material),
+        sandbox.properties(blocker.material),
+    );
+    let (mover_density, blocker_density) = (
+        mover.density(mover_properties).0,
+        blocker.density(blocker_properties).0,
+    );
+    match (
+        mover_properties.movement_type,
+        blocker_properties.movement_type,
+    ) {
+        (MovementType::Solid, _) | (_, MovementType::Solid) => false,
+        (MovementType::Gas, MovementType::Powder) => false,
+        (MovementType::Gas, _) => mover_density < blocker_density,
+        (MovementType::Powder | MovementType::Liquid, _) => mover_density > blocker_density,
+    }
+}
+
+/// Whether a particle of `material` would rest one cell thick at the end of its step,
+/// on top of something else and without any particle of its material above or below.
+fn is_film(step_data: &StepData, material: MaterialId, sandbox: &Sandbox) -> bool {
+    let (x, y) = (step_data.new_x, step_data.new_y);
+    if sandbox
+        .checked_get_i32(x, y + 1)
+        .is_some_and(|p| p.material == material)
+    {
+        return false;
+    }
+
+    match sandbox.checked_get_i32(x, y - 1) {
+        Some(below) => below.material != material,
+        None => sandbox.out_of_bounds_i32(x, y - 1),
+    }
+}
+
+/// Rotation 0 keeps the direction of the velocity, rotations 1 to 4 move sideways.
 fn line_with_rotation(
     start_x: i32,
     start_y: i32,
@@ -167,7 +263,7 @@ fn line(mut x1: i32, mut y1: i32, x2: i32, y2: i32, sandbox: &Sandbox) -> StepDa
     }
     let mut longest = w.abs();
     let mut shortest = h.abs();
-    if !(longest > shortest) {
+    if longest <= shortest {
         longest = h.abs();
         shortest = w.abs();
         if h < 0 {
@@ -200,7 +296,7 @@ fn line(mut x1: i32, mut y1: i32, x2: i32, y2: i32, sandbox: &Sandbox) -> StepDa
         past_y = y1;
 
         numerator += shortest;
-        if !(numerator < longest) {
+        if numerator >= longest {
             numerator -= longest;
             x1 += dx1;
             y1 += dy1;
@@ -1,26 +1,86 @@
-use bevy::{render::color::Color, utils::default};
-use rand::*;
+use serde::Deserialize;
 use std::cmp::Ordering;
 
-#[derive(Clone, Copy, Default)]
+use super::material::MaterialProperties;
+
+/// Per-cell state of a particle. Static properties are shared by every particle
+/// of a material, in the [`MaterialTable`](super::material::MaterialTable).
+#[derive(Clone, Copy)]
 pub struct Particle {
-    pub lifespan: Option<LifeSpan>,
-    pub health: i32,
-    pub velocity: Velocity,
-    pub density: Density,
+    pub material: MaterialId,
     pub color: (u8, u8, u8, u8),
-    pub movement_type: MovementType,
-    pub spread_rate: i32,
-    pub use_gravity: bool,
-    pub acidity: Option<Acidity>,
-    pub corrodable: Option<Corrodable>,
-    pub temperature: Option<Temperature>,
-    pub temperature_changer: Option<TemperatureChanger>,
-    pub burnable: Option<Burnable>,
+    pub velocity: Velocity,
+    pub health: i32,
+    pub temperature: i32,
+    pub burning: bool,
+    /// Counter of the material, a material keeps at most one of them:
+    /// - the water held by a growing particle, spent to grow,
+    /// - the ticks left before an explosive caught in a blast goes off, 0 when it isn't primed,
+    /// - the units of solute dissolved in a solution,
+    /// - the material copied by a clone block, see `copied`.
+    pub counter: i32,
     pub updated: bool,
 }
 
-#[derive(Clone, Copy, PartialEq, Default)]
+impl Particle {
+    /// Material copied by a clone block, from the first particle that touched it.
+    pub fn copied(&self) -> Option<MaterialId> {
+        match self.counter {
+            0 => None,
+            counter => Some(MaterialId((counter - 1) as u16)),
+        }
+    }
+
+    pub fn set_copied(&mut self, material: Option<MaterialId>) {
+        self.counter = material.map_or(0, |material| material.0 as i32 + 1);
+    }
+
+    /// Burning particles heat their neighbors, whatever their material.
+    pub fn temperature_changer(
+        &self,
+        properties: &MaterialProperties,
+    ) -> Option<TemperatureChanger> {
+        match self.burning {
+            true => Some(TemperatureChanger(2)),
+            false => properties.temperature_changer,
+        }
+    }
+
+    /// Dissolved solute makes a solution denser, up to its `density` once saturated.
+    pub fn density(&self, properties: &MaterialProperties) -> Density {
+        match properties.solution {
+            Some(solution) => {
+                let gain = self.counter.max(0) as u32 * solution.density / solution.capacity as u32;
+                Density(properties.density.0.saturating_add(gain))
+            }
+            None => properties.density,
+        }
+    }
+
+    /// Degrees a solution has to cool past the freezing point of its material before freezing.
+    pub fn freezing_drop(&self, properties: &MaterialProperties) -> i32 {
+        properties
+            .solution
+            .map_or(0, |solution| self.counter * solution.freezing_drop)
+    }
+
+    /// Whether an electric particle carries current, solutions need enough solute dissolved.
+    pub fn conducts(&self, properties: &MaterialProperties) -> bool {
+        properties.electric.is_some()
+            && properties
+                .solution
+                .is_none_or(|solution| self.counter >= solution.conductive)
+    }
+}
+
+/// Wind blown by a fan in front of it, in cells per tick.
+#[derive(Clone, Copy, Deserialize)]
+pub struct Fan {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Clone, Copy, PartialEq, Default, Deserialize)]
 pub enum MovementType {
     Solid,
     #[default]
@@ -52,28 +112,32 @@ impl Velocity {
 #[derive(Default, Clone, Copy)]
 pub struct Density(pub u32);
 
+/// Inclusive ranges of the random velocity given to a particle on spawn.
+#[derive(Clone, Copy, Deserialize)]
+pub struct SpawnVelocity {
+    pub x: (i32, i32),
+    pub y: (i32, i32),
+}
+
 #[derive(Clone, Copy)]
 pub struct LifeSpan {
     pub replacement: ParticleReplacement,
 }
 
 impl LifeSpan {
-    fn new(replacement: ParticleReplacement) -> Self {
+    pub fn new(replacement: ParticleReplacement) -> Self {
         Self { replacement }
     }
 }
 
-#[derive(Clone, Copy)]
-pub struct Corrodable(pub i32);
-
 #[derive(Clone, Copy)]
 pub struct ParticleReplacement {
-    pub material: Option<Material>,
+    pub material: Option<MaterialId>,
     pub probability: f32,
 }
 
 impl ParticleReplacement {
-    fn new(material: Option<Material>, probability: f32) -> Self {
+    pub fn new(material: Option<MaterialId>, probability: f32) -> Self {
         Self {
             material,
             probability,
@@ -86,366 +150,163 @@ pub struct Acidity(pub i32);
 
 #[derive(Clone, Copy)]
 pub struct Temperature {
-    pub current: i32,
     pub start_temperature: i32,
+    /// Whether `TemperatureChanger` neighbors can cool the particle down.
     pub coolable: bool,
+    /// Whether `TemperatureChanger` neighbors can heat the particle up.
     pub heatable: bool,
-    pub critical_on_cool: bool,
-    pub replacement_on_critical: ParticleReplacement,
-    pub explosion_radius: i32,
+    /// Degrees past a transition temperature before it happens, so a particle that just
+    /// changed state doesn't flicker back.
+    pub hysteresis: i32,
+    pub melting: Option<PhaseTransition>,
+    pub boiling: Option<PhaseTransition>,
+    pub freezing: Option<PhaseTransition>,
+    pub condensation: Option<PhaseTransition>,
+    pub explosion: Option<Explosion>,
 }
 
 impl Temperature {
-    pub fn new(
-        start_temperature: i32,
-        coolable: bool,
-        heatable: bool,
-        critical_on_cool: bool,
-        replacement_on_critical: ParticleReplacement,
-        explosion_radius: i32,
-    ) -> Self {
-        Self {
-            current: start_temperature,
-            start_temperature,
-            coolable,
-            heatable,
-            critical_on_cool,
-            replacement_on_critical,
-            explosion_radius,
-        }
+    /// Transition of a particle at `temperature`, if it is past one of the thresholds, with the
+    /// freezing point lowered by `freezing_drop`.
+    /// The furthest threshold wins: boiling over melting, and freezing over condensation.
+    pub fn transition(&self, temperature: i32, freezing_drop: i32) -> Option<PhaseTransition> {
+        let heating = [self.boiling, self.melting]
+            .into_iter()
+            .flatten()
+            .find(|transition| temperature >= transition.temperature + self.hysteresis);
+
+        heating.or_else(|| {
+            let freezing = self.freezing.filter(|transition| {
+                temperature <= transition.temperature - freezing_drop - self.hysteresis
+            });
+            freezing.or(self
+                .condensation
+                .filter(|transition| temperature <= transition.temperature - self.hysteresis))
+        })
     }
 }
 
+/// Replaces a particle once its temperature crosses `temperature`. The particle keeps its
+/// temperature in the new state.
+#[derive(Clone, Copy)]
+pub struct PhaseTransition {
+    pub temperature: i32,
+    pub replacement: ParticleReplacement,
+}
+
+#[derive(Clone, Copy)]
+pub struct Explosion {
+    pub temperature: i32,
+    pub radius: i32,
+    /// Heat added to the particles at the center of the blast, less toward its edge.
+    pub heat: i32,
+    pub debris: Option<MaterialId>,
+}
+
 #[derive(Clone, Copy)]
 pub struct TemperatureChanger(pub i32);
 
 #[derive(Clone, Copy)]
 pub struct Burnable {
     pub burn_temperature: i32,
+    /// Minimum number of ticks a particle burns for, it loses one health every tick.
     pub burn_ticks: i32,
-    pub burn_color: (u8, u8, u8, u8),
-    pub cooled_color: (u8, u8, u8, u8),
-    pub burning: bool,
-    pub emission: Option<Material>,
-    pub emit_smoke: bool,
+    /// Chance a touching flame ignites the particle, every tick.
+    pub flammability: f32,
+    pub emission: Option<MaterialId>,
+    pub smoke: Option<MaterialId>,
+    /// What is left once the particle burned out.
+    pub residue: ParticleReplacement,
 }
 
-#[derive(Debug, Clone, Copy)]
-pub enum Material {
-    Sand,
-    Water,
-    Stone,
-    Steam,
-    Wood,
-    Acid,
-    Lava,
-    Smoke,
-    Spark,
-    Igneous,
-    Ash,
-    Oil,
-    Glass,
-    Gunpowder,
-    Tnt,
+/// A liquid holding a dissolved powder, the `solute`, up to `capacity` units per particle. The
+/// `solvent` turns into the solution when it gets a unit of solute, and the solution turns back
+/// into its solvent once it shared its last unit.
+#[derive(Clone, Copy)]
+pub struct Solution {
+    pub solvent: MaterialId,
+    pub solute: MaterialId,
+    pub capacity: i32,
+    /// Density gained once saturated.
+    pub density: u32,
+    /// Degrees the freezing point is lowered by every unit of solute.
+    pub freezing_drop: i32,
+    /// Fewest units of solute for an electric solution to carry current.
+    pub conductive: i32,
+    /// Chance a particle shares a unit of solute with a more dilute neighbor, every tick.
+    pub diffusion: f32,
 }
 
-// https://lospec.com/palette-list/endesga-32
-pub const SAND_COLOR: Color = Color::hsl(36.0, 0.99, 0.60);
-pub const STONE_COLOR: Color = Color::hsl(220.0, 0.20, 0.44);
-pub const WATER_COLOR: Color = Color::hsla(198.0, 1.00, 0.43, 0.7);
-pub const STEAM_COLOR: Color = Color::hsl(217.0, 0.21, 0.63);
-pub const WOOD_COLOR: Color = Color::hsl(5.0, 0.34, 0.34);
-pub const ACID_COLOR: Color = Color::hsla(109.0, 0.52, 0.54, 0.7);
-pub const LAVA_COLOR: Color = Color::hsl(357.0, 0.76, 0.56);
-pub const SMOKE_COLOR: Color = Color::hsl(216.0, 0.29, 0.81);
-pub const IGNEOUS_COLOR: Color = Color::hsl(334.0, 0.23, 0.20);
-pub const ASH_COLOR: Color = Color::hsl(220.0, 0.20, 0.44);
-pub const OIL_COLOR: Color = Color::hsl(39.0, 0.60, 0.84);
-pub const GLASS_COLOR: Color = Color::hsla(184.0, 0.81, 0.57, 0.7);
-pub const GUNPOWDER_COLOR: Color = Color::hsl(216.0, 0.29, 0.81);
-pub const TNT_COLOR: Color = Color::hsl(354.0, 0.62, 0.39);
-
-pub const SPARK_COLORS: [Color; 3] = [
-    Color::hsl(51.0, 0.99, 0.69),
-    Color::hsl(36.0, 0.99, 0.60),
-    Color::hsl(24.0, 0.93, 0.55),
-];
-
-pub const WOOD_BURN_COLORS: [Color; 3] = [
-    Color::hsl(354.0, 0.62, 0.39),
-    Color::hsl(24.0, 0.93, 0.55),
-    Color::hsl(36.0, 0.99, 0.60),
-];
-
-pub fn get_particle(material: Material) -> Particle {
-    let mut particle = match material {
-        Material::Sand => Particle {
-            health: 50,
-            color: format_and_variate_color(SAND_COLOR, 0.04),
-            density: Density(u32::MAX),
-            use_gravity: true,
-            temperature: Some(Temperature::new(
-                50,
-                true,
-                true,
-                false,
-                ParticleReplacement::new(Some(Material::Glass), 1.),
-                0,
-            )),
-            corrodable: Some(Corrodable(50)),
-            ..default()
-        },
-        Material::Glass => Particle {
-            health: 50,
-            color: format_and_variate_color(GLASS_COLOR, 0.),
-            movement_type: MovementType::Solid,
-            density: Density(u32::MAX),
-            use_gravity: true,
-            ..default()
-        },
-
-        Material::Water => Particle {
-            health: 50,
-            color: format_and_variate_color(WATER_COLOR, 0.005),
-            movement_type: MovementType::Liquid,
-            spread_rate: 2,
-            density: Density(1),
-            temperature: Some(Temperature::new(
-                30,
-                false,
-                true,
-                false,
-                ParticleReplacement::new(Some(Material::Steam), 0.8),
-                0,
-            )),
-            temperature_changer: Some(TemperatureChanger(-5)),
-            use_gravity: true,
-            ..default()
-        },
-        Material::Stone => Particle {
-            health: 50,
-            color: format_and_variate_color(STONE_COLOR, 0.),
-            movement_type: MovementType::Solid,
-            density: Density(u32::MAX),
-            use_gravity: true,
-            corrodable: Some(Corrodable(50)),
-            ..default()
-        },
-        Material::Steam => {
-            let health = thread_rng().gen_range(100..120);
-            Particle {
-                health,
-                lifespan: Some(LifeSpan::new(ParticleReplacement {
-                    material: Some(Material::Water),
-                    probability: 0.1,
-                })),
-                color: format_and_variate_color(STEAM_COLOR, 0.04),
-                movement_type: MovementType::Gas,
-                density: Density(0),
-                use_gravity: true,
-                ..default()
-            }
-        }
-        Material::Wood => {
-            let rand_index = thread_rng().gen_range(0..WOOD_BURN_COLORS.len());
-            Particle {
-                health: 50,
-                color: format_and_variate_color(WOOD_COLOR, 0.04),
-                movement_type: MovementType::Solid,
-                density: Density(u32::MAX),
-                use_gravity: true,
-                temperature: Some(Temperature::new(
-                    30,
-                    true,
-                    true,
-                    false,
-                    ParticleReplacement::new(Some(Material::Ash), 0.3),
-                    0,
-                )),
-                burnable: Some(Burnable {
-                    burn_temperature: 100,
-                    burn_ticks: 50,
-                    burn_color: format_and_variate_color(WOOD_BURN_COLORS[rand_index], 0.04),
-                    cooled_color: format_and_variate_color(WOOD_COLOR, 0.04),
-                    burning: false,
-                    emission: Some(Material::Spark),
-                    emit_smoke: true,
-                }),
-                corrodable: Some(Corrodable(50)),
-                ..default()
-            }
-        }
-        Material::Acid => Particle {
-            health: 50,
-            color: format_and_variate_color(ACID_COLOR, 0.04),
-            movement_type: MovementType::Liquid,
-            spread_rate: 1,
-            density: Density(2),
-            acidity: Some(Acidity(5)),
-            use_gravity: true,
-            ..default()
-        },
-        Material::Lava => Particle {
-            health: 50,
-            color: format_and_variate_color(LAVA_COLOR, 0.005),
-            movement_type: MovementType::Liquid,
-            density: Density(5),
-            temperature: Some(Temperature::new(
-                50,
-                true,
-                false,
-                true,
-                ParticleReplacement::new(Some(Material::Igneous), 0.9),
-                0,
-            )),
-            temperature_changer: Some(TemperatureChanger(5)),
-            use_gravity: true,
-            ..default()
-        },
-        Material::Smoke => {
-            let health = thread_rng().gen_range(40..55);
-            Particle {
-                health,
-                lifespan: Some(LifeSpan::new(ParticleReplacement::new(None, 1.))),
-                color: format_and_variate_color(SMOKE_COLOR, 0.05),
-                movement_type: MovementType::Gas,
-                density: Density(0),
-                use_gravity: true,
-                ..default()
-            }
-        }
-        Material::Spark => {
-            let health = thread_rng().gen_range(5..10);
-            let rand_index = thread_rng().gen_range(0..SPARK_COLORS.len());
-            Particle {
-                health,
-                lifespan: Some(LifeSpan::new(ParticleReplacement::new(None, 1.))),
-                color: format_and_variate_color(SPARK_COLORS[rand_index], 0.),
-                movement_type: MovementType::Gas,
-                density: Density(1),
-                temperature_changer: Some(TemperatureChanger(5)),
-                use_gravity: true,
-                ..default()
-            }
-        }
-        Material::Igneous => Particle {
-            health: 50,
-            color: format_and_variate_color(IGNEOUS_COLOR, 0.),
-            movement_type: MovementType::Solid,
-            density: Density(u32::MAX),
-            use_gravity: true,
-            corrodable: Some(Corrodable(50)),
-            ..default()
-        },
-        Material::Ash => Particle {
-            health: 50,
-            color: format_and_variate_color(ASH_COLOR, 0.02),
-            movement_type: MovementType::Powder,
-            density: Density(u32::MAX),
-            use_gravity: true,
-            corrodable: Some(Corrodable(50)),
-            ..default()
-        },
-        Material::Oil => Particle {
-            health: 50,
-            color: format_and_variate_color(OIL_COLOR, 0.),
-            movement_type: MovementType::Liquid,
-            density: Density(2),
-            temperature: Some(Temperature::new(
-                5,
-                false,
-                true,
-                false,
-                ParticleReplacement::new(Some(Material::Spark), 1.),
-                0,
-            )),
-            burnable: Some(Burnable {
-                burn_temperature: 42,
-                burn_ticks: 15,
-                burn_color: (204, 146, 95, 255),
-                cooled_color: format_and_variate_color(OIL_COLOR, 0.),
-                burning: false,
-                emission: None,
-                emit_smoke: false,
-            }),
-            use_gravity: true,
-            ..default()
-        },
-        Material::Gunpowder => Particle {
-            health: 50,
-            color: format_and_variate_color(GUNPOWDER_COLOR, 0.),
-            movement_type: MovementType::Powder,
-            density: Density(u32::MAX),
-            temperature: Some(Temperature::new(
-                1,
-                true,
-                true,
-                false,
-                ParticleReplacement::new(None, 1.),
-                5,
-            )),
-            burnable: Some(Burnable {
-                burn_temperature: 32,
-                burn_ticks: 15,
-                burn_color: (204, 146, 95, 255),
-                cooled_color: format_and_variate_color(GUNPOWDER_COLOR, 0.),
-                burning: false,
-                emission: None,
-                emit_smoke: true,
-            }),
-            use_gravity: true,
-            corrodable: Some(Corrodable(50)),
-            ..default()
-        },
-        Material::Tnt => Particle {
-            health: 50,
-            color: format_and_variate_color(TNT_COLOR, 0.),
-            movement_type: MovementType::Solid,
-            density: Density(u32::MAX),
-            temperature: Some(Temperature::new(
-                1,
-                true,
-                true,
-                false,
-                ParticleReplacement::new(None, 1.),
-                15,
-            )),
-            use_gravity: true,
-            corrodable: Some(Corrodable(50)),
-            ..default()
-        },
-    };
-
-    // Particle spread on spawm
-    let random_velocity_x: i32;
-    let random_velocity_y: i32;
-    match (material, particle.movement_type) {
-        (Material::Spark, _) => {
-            random_velocity_x = thread_rng().gen_range(-6..=6);
-            random_velocity_y = thread_rng().gen_range(-6..=6);
-        }
-        (_, MovementType::Powder | MovementType::Liquid) => {
-            random_velocity_x = thread_rng().gen_range(-3..=3);
-            random_velocity_y = -2;
-        }
-        (_, _) => {
-            return particle;
-        }
-    }
-    particle.velocity = Velocity::new(random_velocity_x, random_velocity_y);
-    particle
+/// A powder dissolving into the solvent of its `solution`, or into the solution itself until
+/// it is saturated.
+#[derive(Clone, Copy)]
+pub struct Soluble {
+    pub solution: MaterialId,
+    pub probability: f32,
 }
 
-fn format_and_variate_color(color: Color, range: f32) -> (u8, u8, u8, u8) {
-    let mut c: Color = color;
-    if range != 0.0 {
-        let mut rng = rand::thread_rng();
-        c.set_l(c.l() + rng.gen_range(-0.04..=0.04));
-    }
-    (
-        (c.r() * 255.0) as u8,
-        (c.g() * 255.0) as u8,
-        (c.b() * 255.0) as u8,
-        (c.a() * 255.0) as u8,
-    )
+/// Spawns its `material` into the empty cells around the particle, each at the `rate` chance
+/// every tick. Without a material, it copies the first particle touching it.
+#[derive(Clone, Copy)]
+pub struct Emitter {
+    pub material: Option<MaterialId>,
+    pub rate: f32,
+}
+
+/// Role of a material in circuits, materials without one are insulators.
+#[derive(Clone, Copy, PartialEq, Deserialize)]
+pub enum Electric {
+    /// Powers the conductors connected to it.
+    PowerSource,
+    /// Carries current, and heats up by `heat` every tick it is powered.
+    Conductor { heat: i32 },
+}
+
+/// A seed resting on one of the `soil` materials, with `water` around it, sprouts into
+/// `material`.
+#[derive(Clone)]
+pub struct Sprout {
+    pub material: MaterialId,
+    pub soil: Vec<MaterialId>,
+    pub water: MaterialId,
+    /// Chance to sprout every tick, once on soil next to water.
+    pub probability: f32,
+}
+
+/// A particle that absorbs the `water` particles it touches, and spends them to grow particles
+/// of `material` upward or along surfaces.
+#[derive(Clone, Copy)]
+pub struct Growth {
+    pub material: MaterialId,
+    /// Chance to grow every tick, while the particle holds water.
+    pub rate: f32,
+    pub water: Option<MaterialId>,
+    /// Most water the particle holds, absorbed water is shared with the growing neighbors.
+    pub capacity: i32,
+    /// What a grown particle becomes instead, like a flower at the tip of a stem.
+    pub bloom: Option<ParticleReplacement>,
 }
+
+/// A particle buried under at least `depth` particles is compressed into `material`.
+#[derive(Clone, Copy)]
+pub struct Packing {
+    pub depth: usize,
+    pub material: MaterialId,
+    pub probability: f32,
+}
+
+/// A particle touching a particle of the `with` material reacts with it: both are replaced by
+/// their product, or removed when it has none.
+#[derive(Clone, Copy)]
+pub struct Reaction {
+    pub with: MaterialId,
+    pub product: Option<MaterialId>,
+    pub neighbor_product: Option<MaterialId>,
+    pub probability: f32,
+    /// Inclusive range the temperature of the reacting particle must be in.
+    pub temperature: Option<(i32, i32)>,
+}
+
+/// Index of a material in the [`MaterialTable`](super::material::MaterialTable).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct MaterialId(pub u16);
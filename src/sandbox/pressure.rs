@@ -0,0 +1,313 @@
+use rand::Rng;
+
+use super::chunk::DirtyRect;
+use super::particle::{MovementType, Velocity};
+use super::sandbox::Sandbox;
+
+/// Difference of height between the surfaces of a liquid body past which liquid flows from
+/// the highest surfaces to the lowest ones, so communicating vessels even out.
+const LEVEL_DIFFERENCE: usize = 3;
+/// Mark of the air cells found connected to the border of the sandbox during this update.
+const OPEN_AIR: u32 = 1;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CellKind {
+    /// Empty cells and gases.
+    Air,
+    Liquid,
+    /// Liquid with air on both sides, falling or splashing, which doesn't carry pressure.
+    Falling,
+    /// Solids and powders, which don't carry pressure.
+    Blocked,
+}
+
+fn is_air(x: usize, y: usize, sandbox: &Sandbox) -> bool {
+    if sandbox.out_of_bounds_usize(x, y) {
+        return false;
+    }
+
+    match sandbox.get(x, y) {
+        Some(particle) => sandbox.properties(particle.material).movement_type == MovementType::Gas,
+        None => true,
+    }
+}
+
+fn cell_kind(x: usize, y: usize, sandbox: &Sandbox) -> CellKind {
+    let movement_type = match sandbox.get(x, y) {
+        Some(particle) => sandbox.properties(particle.material).movement_type,
+        None => return CellKind::Air,
+    };
+
+    match movement_type {
+        MovementType::Gas => CellKind::Air,
+        MovementType::Liquid => {
+            if is_air(x.overflowing_sub(1).0, y, sandbox) && is_air(x + 1, y, sandbox) {
+                CellKind::Falling
+            } else {
+                CellKind::Liquid
+            }
+        }
+        MovementType::Solid | MovementType::Powder => CellKind::Blocked,
+    }
+}
+
+/// Whether the cell above a liquid cell leaves it a free surface.
+fn is_open_above(x: usize, y: usize, sandbox: &Sandbox) -> bool {
+    y + 1 < sandbox.height()
+        && matches!(
+            cell_kind(x, y + 1, sandbox),
+            CellKind::Air | CellKind::Falling
+        )
+}
+
+/// Measures the connected bodies of liquid and air around the cells changed during the last
+/// tick, those which didn't change are already balanced:
+/// - liquid is pushed out of the lowest surfaces of a body, taken from its highest surfaces,
+///   so the depth under the highest surface is the pressure pushing it,
+/// - the air cells of a sealed pocket hold the share of the pocket filled with gas, which
+///   bursts the solids around it whose `burst_pressure` is reached.
+///
+/// The air is only measured around the gases and the solids which can burst, and stops at the
+/// border of the sandbox, so the open sky isn't walked through every tick.
+///
+/// Must run on the whole sandbox, after the chunks are updated so the liquid it moves is
+/// measured again next tick, until the body is level. Returns the number of cells measured.
+pub fn update_pressure(sandbox: &mut Sandbox) -> usize {
+    let dirty_rects: Vec<DirtyRect> = sandbox
+        .chunks()
+        .iter()
+        .filter_map(|chunk| chunk.dirty_rect())
+        .collect();
+    // Nothing moved since the last tick, so the pressure didn't change either
+    if dirty_rects.is_empty() {
+        return 0;
+    }
+
+    let (width, height) = (sandbox.width(), sandbox.height());
+    let mut visited = sandbox.take_marks();
+    let mut body = Vec::new();
+    let mut stack = Vec::new();
+    let mut surfaces = Vec::new();
+    let mut flows = Vec::new();
+    let mut burst = Vec::new();
+    let mut measured = 0;
+
+    for rect in dirty_rects {
+        let measure_air = holds_pressurized(&rect, sandbox);
+        for start_y in rect.min_y..=rect.max_y {
+            for start_x in rect.min_x..=rect.max_x {
+                let start = sandbox.to_index(start_x, start_y);
+                if visited.is_marked(start) {
+                    continue;
+                }
+
+                // Air left unmeasured isn't marked, the air of another rect may reach it
+                let kind = cell_kind(start_x, start_y, sandbox);
+                if kind == CellKind::Blocked
+                    || kind == CellKind::Falling
+                    || (kind == CellKind::Air && !measure_air)
+                {
+                    continue;
+                }
+                visited.mark(start, 0);
+
+                let mut open = false;
+                let mut gas_count = 0;
+
+                body.clear();
+                surfaces.clear();
+                stack.push((start_x, start_y));
+                while let Some((x, y)) = stack.pop() {
+                    body.push((x, y));
+                    measured += 1;
+
+                    match kind {
+                        CellKind::Liquid => {
+                            if is_open_above(x, y, sandbox) {
+                                surfaces.push((x, y));
+                            }
+                        }
+                        _ => {
+                            if sandbox.get(x, y).is_some() {
+                                gas_count += 1;
+                            }
+                            if x == 0 || y == 0 || x == width - 1 || y == height - 1 {
+                                open = true;
+                                break;
+                            }
+                        }
+                    }
+
+                    for (neighbor_x, neighbor_y) in [
+                        (x.overflowing_sub(1).0, y),
+                        (x + 1, y),
+                        (x, y.overflowing_sub(1).0),
+                        (x, y + 1),
+                    ] {
+                        if neighbor_x >= width || neighbor_y >= height {
+                            continue;
+                        }
+
+                        let index = sandbox.to_index(neighbor_x, neighbor_y);
+                        match visited.get(index) {
+                            // Reached the air of another body found open
+                            Some(OPEN_AIR) if kind == CellKind::Air => open = true,
+                            Some(_) => {}
+                            None => {
+                                if cell_kind(neighbor_x, neighbor_y, sandbox) == kind {
+                                    visited.mark(index, 0);
+                                    stack.push((neighbor_x, neighbor_y));
+                                }
+                            }
+                        }
+                    }
+                    if open {
+                        break;
+                    }
+                }
+
+                // The rest of the open air isn't walked, but later bodies reaching the part
+                // walked know it's open
+                if open {
+                    for &(x, y) in body.iter().chain(stack.iter()) {
+                        visited.mark(sandbox.to_index(x, y), OPEN_AIR);
+                    }
+                    stack.clear();
+                    continue;
+                }
+
+                match kind {
+                    CellKind::Liquid => {
+                        // Pairs the highest surfaces with the lowest ones
+                        surfaces.sort_unstable_by_key(|&(x, y)| (y, x));
+                        for (&low, &high) in surfaces.iter().zip(surfaces.iter().rev()) {
+                            if high.1 < low.1 + LEVEL_DIFFERENCE {
+                                break;
+                            }
+
+                            // Stacks on top of the liquid already pushed out
+                            let mut target_y = low.1 + 1;
+                            while target_y < high.1
+                                && cell_kind(low.0, target_y, sandbox) == CellKind::Falling
+                            {
+                                target_y += 1;
+                            }
+                            if target_y < high.1 {
+                                flows.push((high, (low.0, target_y)));
+                            }
+                        }
+                    }
+                    _ => {
+                        let fill = gas_count as f32 / body.len() as f32;
+                        for &(x, y) in body.iter() {
+                            for (neighbor_x, neighbor_y) in [
+                                (x.overflowing_sub(1).0, y),
+                                (x + 1, y),
+                                (x, y.overflowing_sub(1).0),
+                                (x, y + 1),
+                            ] {
+                                let burst_pressure =
+                                    match sandbox.checked_get(neighbor_x, neighbor_y) {
+                                        Some(particle) => {
+                                            sandbox.properties(particle.material).burst_pressure
+                                        }
+                                        None => None,
+                                    };
+
+                                if burst_pressure
+                                    .is_some_and(|burst_pressure| fill >= burst_pressure)
+                                {
+                                    burst.push((neighbor_x, neighbor_y));
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    sandbox.set_marks(visited);
+
+    for ((from_x, from_y), (to_x, to_y)) in flows {
+        if sandbox.get(to_x, to_y).is_some() {
+            continue;
+        }
+
+        if let Some(mut particle) = sandbox.get(from_x, from_y).copied() {
+            // Viscous liquids take longer to even out, the body is measured again next tick
+            let viscosity = sandbox.properties(particle.material).viscosity;
+            if sandbox.rng().gen_bool(viscosity as f64) {
+                sandbox.wake(from_x, from_y);
+                continue;
+            }
+
+            particle.velocity = Velocity::new(0, 0);
+            sandbox.set(from_x, from_y, None);
+            sandbox.set(to_x, to_y, Some(particle));
+        }
+    }
+
+    for (x, y) in burst {
+        sandbox.set(x, y, None);
+    }
+    measured
+}
+
+/// Whether the rect holds a gas, or a solid bursting under pressure, so the air around it is
+/// measured.
+fn holds_pressurized(rect: &DirtyRect, sandbox: &Sandbox) -> bool {
+    (rect.min_y..=rect.max_y).any(|y| {
+        (rect.min_x..=rect.max_x).any(|x| {
+            sandbox.get(x, y).is_some_and(|particle| {
+                let properties = sandbox.properties(particle.material);
+                properties.movement_type == MovementType::Gas || properties.burst_pressure.is_some()
+            })
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sandbox::{step_sandbox, UpdateMode};
+
+    /// Fills the rect with the material, `min` inclusive and `max` exclusive.
+    fn fill(sandbox: &mut Sandbox, material: &str, min: (usize, usize), max: (usize, usize)) {
+        let material = sandbox.materials().id(material).unwrap();
+        for y in min.1..max.1 {
+            for x in min.0..max.0 {
+                let particle = sandbox.get_particle(material);
+                sandbox.set(x, y, Some(particle));
+            }
+        }
+    }
+
+    #[test]
+    fn open_sky_isnt_walked() {
+        for material in ["Water", "Smoke"] {
+            let mut sandbox = Sandbox::new(512, 512, 1);
+            fill(&mut sandbox, "Bedrock", (0, 0), (512, 1));
+            step_sandbox(&mut sandbox, UpdateMode::SingleThreaded);
+
+            fill(&mut sandbox, material, (256, 400), (257, 401));
+            sandbox.update_chunks();
+            assert!(update_pressure(&mut sandbox) < 1024, "{material}");
+        }
+    }
+
+    #[test]
+    fn sealed_pocket_bursts() {
+        let mut sandbox = Sandbox::new(64, 64, 1);
+        fill(&mut sandbox, "Glass", (20, 20), (32, 32));
+        fill(&mut sandbox, "Smoke", (21, 21), (31, 31));
+        sandbox.update_chunks();
+        update_pressure(&mut sandbox);
+
+        let glass = sandbox.materials().id("Glass").unwrap();
+        let walls = (20..32)
+            .flat_map(|y| (20..32).map(move |x| (x, y)))
+            .filter(|&(x, y)| sandbox.get(x, y).is_some_and(|p| p.material == glass))
+            .count();
+        assert!(walls < 12 * 12 - 10 * 10);
+    }
+}
@@ -0,0 +1,47 @@
+use rand::Rng;
+
+use super::sandbox::Sandbox;
+
+/// Checks the reactions of the particle against its neighbors, and applies the first one
+/// that succeeds. Returns true if the particle was replaced.
+pub fn step_reactions(x: usize, y: usize, sandbox: &mut Sandbox) -> bool {
+    let particle = *sandbox.get(x, y).unwrap();
+    let reaction_count = sandbox.properties(particle.material).reactions.len();
+
+    for index in 0..reaction_count {
+        let reaction = sandbox.properties(particle.material).reactions[index];
+
+        if let Some((min, max)) = reaction.temperature {
+            if particle.temperature < min || particle.temperature > max {
+                continue;
+            }
+        }
+
+        for (neighbor_x, neighbor_y) in [
+            (x.overflowing_sub(1).0, y),
+            (x + 1, y),
+            (x, y.overflowing_sub(1).0),
+            (x, y + 1),
+        ] {
+            match sandbox.checked_get(neighbor_x, neighbor_y) {
+                Some(neighbor) if neighbor.material == reaction.with => {}
+                _ => continue,
+            }
+
+            if !sandbox.rng().gen_bool(reaction.probability as f64) {
+                continue;
+            }
+
+            let product = reaction
+                .product
+                .map(|material| sandbox.get_particle(material));
+            let neighbor_product = reaction
+                .neighbor_product
+                .map(|material| sandbox.get_particle(material));
+            sandbox.set(x, y, product);
+            sandbox.set(neighbor_x, neighbor_y, neighbor_product);
+            return true;
+        }
+    }
+    false
+}
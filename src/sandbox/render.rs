@@ -1,20 +1,75 @@
 use bevy::prelude::*;
+use bevy::render::{render_resource::*, texture::ImageSampler};
+use std::f32::consts::PI;
 
 use super::sandbox::Sandbox;
+use super::simulation::update_particles;
+use super::{spawn_sandbox, SandboxSettings};
 
 const BACKGROUND_COLOR: (u8, u8, u8, u8) = (30, 30, 46, 255);
+/// Added to the color channels of powered particles, so circuits light up.
+const POWERED_GLOW: u8 = 60;
+
+/// Renders the sandbox spawned by [`SandboxPlugin`](super::SandboxPlugin) to a sprite.
+pub struct SandboxRenderPlugin;
+
+impl Plugin for SandboxRenderPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, spawn_sandbox_sprite.after(spawn_sandbox))
+            .add_systems(Update, render_particles.after(update_particles));
+    }
+}
+
+pub fn spawn_sandbox_sprite(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    sandbox: Query<Entity, With<Sandbox>>,
+    settings: Res<SandboxSettings>,
+) {
+    let (width, height) = settings.size();
+    let image_handle = {
+        let mut image = Image::new_fill(
+            Extent3d {
+                width: width as u32,
+                height: height as u32,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            &[0, 0, 0, 0],
+            TextureFormat::Rgba8UnormSrgb,
+        );
+        image.sampler = ImageSampler::nearest();
+        images.add(image)
+    };
+
+    let sandbox = sandbox.get_single().expect("Sandbox should exists");
+    commands.entity(sandbox).insert(SpriteBundle {
+        texture: image_handle,
+        transform: Transform {
+            translation: settings.translation.extend(1.0),
+            scale: Vec3::new(settings.cell_size, settings.cell_size, 1.0),
+            rotation: Quat::from_euler(EulerRot::XYZ, 0.0, PI, PI),
+        },
+        ..Default::default()
+    });
+}
 
 pub fn render_particles(
     mut images: ResMut<Assets<Image>>,
-    mut sandbox: Query<(&mut Sandbox, &Handle<Image>)>,
+    sandbox: Query<(&Sandbox, &Handle<Image>)>,
 ) {
-    let (mut sandbox, image_handle) = sandbox.get_single_mut().expect("Sandbox should exists");
+    let (sandbox, image_handle) = sandbox.get_single().expect("Sandbox should exists");
 
     let image = images.get_mut(image_handle).unwrap();
     for y in 0..sandbox.height() {
         for x in 0..sandbox.width() {
-            let particle = sandbox.get_mut(x, y);
-            let color = match particle {
+            let color = match sandbox.get(x, y) {
+                Some(p) if sandbox.is_powered(x, y) => (
+                    p.color.0.saturating_add(POWERED_GLOW),
+                    p.color.1.saturating_add(POWERED_GLOW),
+                    p.color.2.saturating_add(POWERED_GLOW),
+                    p.color.3,
+                ),
                 Some(p) => p.color,
                 None => BACKGROUND_COLOR,
             };
@@ -0,0 +1,512 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::f32::consts::FRAC_PI_2;
+
+use super::chunk::DirtyRect;
+use super::marks::CellMarks;
+use super::particle::{MovementType, Particle, Velocity};
+use super::sandbox::Sandbox;
+
+/// Fastest a body moves, in cells per tick along each axis.
+const MAX_SPEED: i32 = 8;
+/// Largest angle a body tips over by in a tick, in radians.
+const MAX_TIP_ANGLE: f32 = 0.3;
+
+/// Rigid bodies of the sandbox, by the id their particles hold. Bodies are kept between ticks,
+/// and only found again around the cells that changed.
+#[derive(Default)]
+pub struct RigidBodies {
+    bodies: BTreeMap<u32, Body>,
+    /// Body of the particle in each cell, so the particles don't hold it.
+    owners: HashMap<(usize, usize), u32>,
+    /// Id of the last body created, ids start from 1.
+    last_id: u32,
+}
+
+impl RigidBodies {
+    /// Records the cells of the body as its own, once it moved.
+    fn claim(&mut self, id: u32) {
+        for &cell in self.bodies[&id].cells.iter() {
+            self.owners.insert(cell, id);
+        }
+    }
+
+    /// Picks an id no body holds, nor the `reserved` ones.
+    fn next_id<T>(&mut self, reserved: &HashMap<u32, T>) -> u32 {
+        loop {
+            self.last_id = self.last_id.wrapping_add(1).max(1);
+            if !self.bodies.contains_key(&self.last_id) && !reserved.contains_key(&self.last_id) {
+                return self.last_id;
+            }
+        }
+    }
+}
+
+struct Body {
+    cells: Vec<(usize, usize)>,
+    /// Position of every particle in the body, in the order of `cells`, before the body rotated.
+    offsets: Vec<(i32, i32)>,
+    /// Rotation of the body, in radians.
+    angle: f32,
+    /// Smallest rect holding the cells.
+    bounds: DirtyRect,
+}
+
+impl Body {
+    fn new(cells: Vec<(usize, usize)>, offsets: Vec<(i32, i32)>, angle: f32) -> Self {
+        let bounds = bounds(&cells);
+        Self {
+            cells,
+            offsets,
+            angle,
+            bounds,
+        }
+    }
+}
+
+fn bounds(cells: &[(usize, usize)]) -> DirtyRect {
+    cells.iter().fold(
+        DirtyRect::new(usize::MAX, usize::MAX, 0, 0),
+        |bounds, &(x, y)| bounds.union(&DirtyRect::new(x, y, x, y)),
+    )
+}
+
+/// Moves the rigid bodies: every group of connected `rigid` particles moves as one piece.
+/// A body touching the border of the sandbox or an `anchored` particle stays in place. Other
+/// bodies:
+/// - fall when nothing holds them, and float on liquids denser than them,
+/// - tip over the edge of what holds them when their center of mass is past it,
+/// - slide with the velocity their particles got, from explosions for example.
+///
+/// Bodies next to the cells changed since the last tick are found again, so a body cut in two
+/// by acid or an explosion breaks apart, and only those are moved: nothing changed around the
+/// others, which are at rest. Must run on the whole sandbox, after the chunks are updated, so
+/// the bodies that moved are checked again next tick.
+pub fn update_rigid_bodies(sandbox: &mut Sandbox) {
+    let dirty_rects: Vec<DirtyRect> = sandbox
+        .chunks()
+        .iter()
+        .filter_map(|chunk| chunk.dirty_rect())
+        .collect();
+    // Nothing moved since the last tick, so the bodies didn't either
+    if dirty_rects.is_empty() {
+        return;
+    }
+
+    let mut bodies = sandbox.take_rigid_bodies();
+    let mut owner = sandbox.take_marks();
+    for id in find_bodies(&dirty_rects, &mut bodies, &mut owner, sandbox) {
+        step_body(id, &mut bodies, &mut owner, sandbox);
+        bodies.claim(id);
+    }
+    sandbox.set_marks(owner);
+    sandbox.set_rigid_bodies(bodies);
+}
+
+/// Flood fills again the bodies next to the dirty rects, and the rigid particles in them,
+/// diagonals included so a tipped body stays in one piece. Bodies touching each other stay
+/// apart, while new rigid particles join the bodies they touch, and weld them together.
+/// Marks the cells of the bodies found with their id, and returns their ids.
+fn find_bodies(
+    dirty_rects: &[DirtyRect],
+    bodies: &mut RigidBodies,
+    owner: &mut CellMarks,
+    sandbox: &mut Sandbox,
+) -> Vec<u32> {
+    let (width, height) = (sandbox.width(), sandbox.height());
+
+    let touched: Vec<u32> = bodies
+        .bodies
+        .iter()
+        .filter(|(_, body)| {
+            let reach = body.bounds.expand(1, width, height);
+            dirty_rects
+                .iter()
+                .any(|rect| rect.intersection(&reach).is_some())
+        })
+        .map(|(&id, _)| id)
+        .collect();
+
+    // Which body the particles of the bodies found again were in, and where
+    let mut angles = HashMap::new();
+    let mut reused = HashSet::new();
+    let mut places = HashMap::new();
+    let mut starts = Vec::new();
+    for id in touched {
+        let body = bodies.bodies.remove(&id).unwrap();
+        angles.insert(id, body.angle);
+        for (&cell, &offset) in body.cells.iter().zip(body.offsets.iter()) {
+            bodies.owners.remove(&cell);
+            places.insert(cell, (id, offset));
+            starts.push(cell);
+        }
+    }
+    for rect in dirty_rects {
+        for y in rect.min_y..=rect.max_y {
+            for x in rect.min_x..=rect.max_x {
+                starts.push((x, y));
+            }
+        }
+    }
+
+    // Particles of the bodies left untouched keep to their body, the others hold the id of
+    // their previous body, 0 for new particles
+    let untouched = std::mem::take(&mut bodies.owners);
+    let body_at = |x: usize, y: usize, sandbox: &Sandbox| {
+        sandbox
+            .checked_get(x, y)
+            .filter(|particle| sandbox.properties(particle.material).rigid)
+            .filter(|_| !untouched.contains_key(&(x, y)))
+            .map(|_| places.get(&(x, y)).map_or(0, |&(id, _)| id))
+    };
+    let mut found = Vec::new();
+    let mut stack = Vec::new();
+    for (start_x, start_y) in starts {
+        let start = sandbox.to_index(start_x, start_y);
+        let previous = match body_at(start_x, start_y, sandbox) {
+            Some(previous) if !owner.is_marked(start) => previous,
+            _ => continue,
+        };
+
+        let mut cells = Vec::new();
+        owner.mark(start, 0);
+        stack.push((start_x, start_y));
+        while let Some((x, y)) = stack.pop() {
+            cells.push((x, y));
+            let cell_body = body_at(x, y, sandbox).unwrap();
+
+            for offset_y in -1..=1 {
+                for offset_x in -1..=1 {
+                    let (neighbor_x, neighbor_y) = (
+                        (x as i32 + offset_x) as usize,
+                        (y as i32 + offset_y) as usize,
+                    );
+                    if neighbor_x >= width || neighbor_y >= height {
+                        continue;
+                    }
+
+                    let neighbor = sandbox.to_index(neighbor_x, neighbor_y);
+                    if owner.is_marked(neighbor) {
+                        continue;
+                    }
+
+                    let joins =
+                        body_at(neighbor_x, neighbor_y, sandbox).is_some_and(|neighbor_body| {
+                            cell_body == 0 || neighbor_body == 0 || neighbor_body == cell_body
+                        });
+                    if joins {
+                        owner.mark(neighbor, 0);
+                        stack.push((neighbor_x, neighbor_y));
+                    }
+                }
+            }
+        }
+
+        // New and welded bodies start over from their current shape, the pieces of a body
+        // broken apart keep its shape, and the first one its id
+        let body = match angles.get(&previous) {
+            Some(&angle)
+                if cells.iter().all(|cell| {
+                    places
+                        .get(cell)
+                        .is_some_and(|&(cell_body, _)| cell_body == previous)
+                }) =>
+            {
+                let offsets = cells.iter().map(|cell| places[cell].1).collect();
+                Body::new(cells, offsets, angle)
+            }
+            _ => {
+                let (min_x, min_y) = cells
+                    .iter()
+                    .fold((usize::MAX, usize::MAX), |(min_x, min_y), &(x, y)| {
+                        (min_x.min(x), min_y.min(y))
+                    });
+                let offsets = cells
+                    .iter()
+                    .map(|&(x, y)| ((x - min_x) as i32, (y - min_y) as i32))
+                    .collect();
+                Body::new(cells, offsets, 0.0)
+            }
+        };
+        let id = match angles.contains_key(&previous) && reused.insert(previous) {
+            true => previous,
+            false => bodies.next_id(&angles),
+        };
+
+        for &(x, y) in body.cells.iter() {
+            owner.mark(sandbox.to_index(x, y), id);
+        }
+        bodies.bodies.insert(id, body);
+        found.push(id);
+    }
+    bodies.owners = untouched;
+    found
+}
+
+fn step_body(id: u32, bodies: &mut RigidBodies, owner: &mut CellMarks, sandbox: &mut Sandbox) {
+    let (width, height) = (sandbox.width(), sandbox.height());
+    let body = bodies.bodies.get_mut(&id).unwrap();
+    let anchored = |x: usize, y: usize| {
+        sandbox
+            .checked_get(x, y)
+            .is_some_and(|particle| sandbox.properties(particle.material).anchored)
+    };
+    if body.cells.iter().any(|&(x, y)| {
+        x == 0
+            || y == 0
+            || x == width - 1
+            || y == height - 1
+            || anchored(x - 1, y)
+            || anchored(x + 1, y)
+            || anchored(x, y - 1)
+            || anchored(x, y + 1)
+    }) {
+        return;
+    }
+
+    let count = body.cells.len() as f64;
+    let mut density = 0.0;
+    let mut velocity = (0, 0);
+    for &(x, y) in body.cells.iter() {
+        let particle = sandbox.get(x, y).unwrap();
+        density += sandbox.properties(particle.material).density.0 as f64 / count;
+        velocity.0 += particle.velocity.x;
+        velocity.1 += particle.velocity.y;
+    }
+    let mut velocity = Velocity::new(
+        velocity.0 / body.cells.len() as i32,
+        velocity.1 / body.cells.len() as i32,
+    );
+
+    // Cells under the body holding it, and whether a denser liquid holds it or lies on top of it
+    let mut supports = Vec::new();
+    let mut floating = false;
+    let mut submerged = false;
+    for &(x, y) in body.cells.iter() {
+        if let Some(below) = sandbox.checked_get(x, y - 1) {
+            if owner.get(sandbox.to_index(x, y - 1)) != Some(id) {
+                let properties = sandbox.properties(below.material);
+                let holds = match properties.movement_type {
+                    MovementType::Solid | MovementType::Powder => true,
+                    MovementType::Liquid => density <= below.density(properties).0 as f64,
+                    MovementType::Gas => false,
+                };
+                if holds {
+                    supports.push((x, y));
+                    floating |= properties.movement_type == MovementType::Liquid;
+                }
+            }
+        }
+
+        if let Some(above) = sandbox.checked_get(x, y + 1) {
+            let properties = sandbox.properties(above.material);
+            if properties.movement_type == MovementType::Liquid
+                && density < above.density(properties).0 as f64
+            {
+                submerged = true;
+            }
+        }
+    }
+
+    if submerged {
+        velocity.y = 1;
+    } else if supports.is_empty() {
+        velocity.y = (velocity.y - 1).max(-MAX_SPEED);
+    } else {
+        velocity.y = velocity.y.max(0);
+        velocity.zero_out_x();
+    }
+    velocity.x = velocity.x.clamp(-MAX_SPEED, MAX_SPEED);
+
+    let mut moved = false;
+    for step in 0..velocity.x.abs().max(velocity.y.abs()) {
+        if step < velocity.x.abs() {
+            let offset = velocity.x.signum();
+            match translate(id, body, owner, (offset, 0), sandbox) {
+                true => moved = true,
+                false => velocity.x = 0,
+            }
+        }
+        if step < velocity.y.abs() {
+            let offset = velocity.y.signum();
+            match translate(id, body, owner, (0, offset), sandbox) {
+                true => moved = true,
+                false => velocity.y = 0,
+            }
+        }
+    }
+
+    if !moved && !supports.is_empty() && !floating {
+        tip_over(id, body, owner, &supports, sandbox);
+    }
+
+    for &(x, y) in body.cells.iter() {
+        sandbox.get_mut(x, y).unwrap().velocity = velocity;
+    }
+}
+
+fn translate(
+    id: u32,
+    body: &mut Body,
+    owner: &mut CellMarks,
+    offset: (i32, i32),
+    sandbox: &mut Sandbox,
+) -> bool {
+    let targets: Vec<(i32, i32)> = body
+        .cells
+        .iter()
+        .map(|&(x, y)| (x as i32 + offset.0, y as i32 + offset.1))
+        .collect();
+    transform(id, body, owner, &targets, sandbox)
+}
+
+/// Rotates the body around the edge of its supports when its center of mass is past them,
+/// so the far end moves by about one cell. A body that can't rotate slides off the edge.
+fn tip_over(
+    id: u32,
+    body: &mut Body,
+    owner: &mut CellMarks,
+    supports: &[(usize, usize)],
+    sandbox: &mut Sandbox,
+) {
+    let cells = &body.cells;
+    let center = cells.iter().fold(0.0, |sum, &(x, _)| sum + x as f32) / cells.len() as f32;
+
+    let left = *supports.iter().min_by_key(|&&(x, y)| (x, y)).unwrap();
+    let right = *supports
+        .iter()
+        .max_by_key(|&&(x, y)| (x, usize::MAX - y))
+        .unwrap();
+    let (pivot, direction) = if center < left.0 as f32 - 0.5 {
+        (left, 1.0)
+    } else if center > right.0 as f32 + 0.5 {
+        (right, -1.0)
+    } else {
+        return;
+    };
+
+    let reach = cells
+        .iter()
+        .map(|&(x, y)| {
+            let (dx, dy) = (x as f32 - pivot.0 as f32, y as f32 - pivot.1 as f32);
+            (dx * dx + dy * dy).sqrt()
+        })
+        .fold(1.0, f32::max);
+
+    // The body is drawn again from its original shape, so the rounding errors don't add up
+    let pivot_place = cells.iter().position(|&cell| cell == pivot).unwrap();
+    let angle = body.angle + direction * (1.0 / reach).min(MAX_TIP_ANGLE);
+    let pivot_offset = rotate(body.offsets[pivot_place], angle);
+    let origin = (
+        pivot.0 as i32 - pivot_offset.0,
+        pivot.1 as i32 - pivot_offset.1,
+    );
+
+    let targets: Vec<(i32, i32)> = body
+        .offsets
+        .iter()
+        .map(|&offset| {
+            let offset = rotate(offset, angle);
+            (origin.0 + offset.0, origin.1 + offset.1)
+        })
+        .collect();
+
+    if transform(id, body, owner, &targets, sandbox) {
+        body.angle = angle;
+    } else {
+        translate(id, body, owner, (-direction as i32, 0), sandbox);
+    }
+}
+
+/// Rotates a cell offset by quarter turns, then by the rest of the angle with three shears.
+/// Every offset lands on a different cell, so no particle is lost.
+fn rotate(offset: (i32, i32), angle: f32) -> (i32, i32) {
+    let quarters = (angle / FRAC_PI_2).round();
+    let rest = angle - quarters * FRAC_PI_2;
+
+    let (mut x, mut y) = offset;
+    for _ in 0..(quarters as i32).rem_euclid(4) {
+        (x, y) = (-y, x);
+    }
+
+    let shear = -(rest / 2.0).tan();
+    let lift = rest.sin();
+    x += (shear * y as f32).round() as i32;
+    y += (lift * x as f32).round() as i32;
+    x += (shear * y as f32).round() as i32;
+    (x, y)
+}
+
+/// Moves every particle of the body to its target cell, if they are all free or hold a fluid.
+/// The displaced fluids fill the cells the body leaves.
+fn transform(
+    id: u32,
+    body: &mut Body,
+    owner: &mut CellMarks,
+    targets: &[(i32, i32)],
+    sandbox: &mut Sandbox,
+) -> bool {
+    let mut displaced = Vec::new();
+    for &(x, y) in targets.iter() {
+        if sandbox.out_of_bounds_i32(x, y) {
+            return false;
+        }
+        let (x, y) = (x as usize, y as usize);
+        if owner.get(sandbox.to_index(x, y)) == Some(id) {
+            continue;
+        }
+
+        match sandbox.get(x, y) {
+            None => displaced.push((x, y)),
+            Some(particle) => match sandbox.properties(particle.material).movement_type {
+                MovementType::Liquid | MovementType::Gas => displaced.push((x, y)),
+                MovementType::Solid | MovementType::Powder => return false,
+            },
+        }
+    }
+
+    let targets: Vec<(usize, usize)> = targets
+        .iter()
+        .map(|&(x, y)| (x as usize, y as usize))
+        .collect();
+    let target_set: HashSet<(usize, usize)> = targets.iter().copied().collect();
+    let cells = std::mem::take(&mut body.cells);
+    let mut vacated: Vec<(usize, usize)> = cells
+        .iter()
+        .copied()
+        .filter(|cell| !target_set.contains(cell))
+        .collect();
+
+    // Pairs the displaced cells with the vacated ones of the same column, or row
+    if targets.iter().zip(cells.iter()).all(|(t, c)| t.0 == c.0) {
+        displaced.sort_unstable();
+        vacated.sort_unstable();
+    } else {
+        displaced.sort_unstable_by_key(|&(x, y)| (y, x));
+        vacated.sort_unstable_by_key(|&(x, y)| (y, x));
+    }
+
+    let particles: Vec<Particle> = cells
+        .iter()
+        .map(|&(x, y)| *sandbox.get(x, y).unwrap())
+        .collect();
+    let fluids: Vec<Option<Particle>> = displaced
+        .iter()
+        .map(|&(x, y)| sandbox.get(x, y).copied())
+        .collect();
+
+    for &(x, y) in cells.iter() {
+        owner.unmark(sandbox.to_index(x, y));
+        sandbox.set(x, y, None);
+    }
+    for (&(x, y), particle) in targets.iter().zip(particles) {
+        owner.mark(sandbox.to_index(x, y), id);
+        sandbox.set(x, y, Some(particle));
+    }
+    for (&(x, y), fluid) in vacated.iter().zip(fluids) {
+        sandbox.set(x, y, fluid);
+    }
+
+    body.bounds = bounds(&targets);
+    body.cells = targets;
+    true
+}
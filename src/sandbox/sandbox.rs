@@ -1,21 +1,174 @@
-use bevy::prelude::Component;
+use bevy::prelude::{Component, Vec2};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::collections::BTreeSet;
+use std::sync::Arc;
 
-use super::particle::Particle;
+use super::chunk::{Chunk, DirtyRect, CHUNK_SIZE};
+use super::marks::CellMarks;
+use super::material::{MaterialProperties, MaterialTable};
+use super::particle::{Electric, MaterialId, Particle};
+use super::rigid_body::RigidBodies;
+use super::wind::WIND_CELL_SIZE;
 
+/// Temperature of the empty cells, unless configured otherwise.
+pub const DEFAULT_AMBIENT_TEMPERATURE: i32 = 20;
+
+/// Grid of particles. Coordinates are always expressed in the full sandbox space,
+/// but a sandbox can hold only a `region` of it (see [`Sandbox::window`]).
 #[derive(Component)]
 pub struct Sandbox {
     width: usize,
     height: usize,
+    region: DirtyRect,
     particles: Vec<Option<Particle>>,
+    chunks_width: usize,
+    chunks: Vec<Chunk>,
+    rng: StdRng,
+    materials: Arc<MaterialTable>,
+    ambient_temperature: i32,
+    /// Marks of the passes over the whole sandbox, like the pressure update.
+    marks: CellMarks,
+    /// Cells of the power sources, kept up to date as particles are set, so the circuits are
+    /// found without going over the whole sandbox.
+    power_sources: BTreeSet<(usize, usize)>,
+    /// Cells of the fans, kept up to date like the power sources.
+    fans: BTreeSet<(usize, usize)>,
+    /// Cells powered by the last electricity update (see `update_electricity`).
+    powered: CellMarks,
+    /// Rigid bodies found by the last rigid bodies update (see `update_rigid_bodies`).
+    rigid_bodies: RigidBodies,
+    /// Cells of the crumbling clusters found holding to an anchor since the last
+    /// `clear_anchored` (see `step_integrity`).
+    anchored: CellMarks,
+    /// Wind of every `WIND_CELL_SIZE` wide square of cells, in cells per tick, updated at the
+    /// start of each tick (see `update_wind`).
+    wind: Vec<Vec2>,
+    /// Squares the wind field covers, the ones over the `region`.
+    wind_squares: DirtyRect,
+    /// Wind the field relaxes toward.
+    ambient_wind: Vec2,
+    /// Pushes added to the wind during the tick, applied by the next wind update.
+    gusts: Vec<(usize, usize, Vec2)>,
+    /// Crumbling particles whose cluster goes on outside of the window they were exposed in,
+    /// searched again on the whole sandbox once the windows are merged.
+    deferred_clusters: Vec<(usize, usize)>,
 }
 
 impl Sandbox {
-    pub fn new(width: usize, height: usize) -> Self {
+    /// Every random decision of the simulation goes through the sandbox random generator,
+    /// so the same `seed` and inputs always produce the same grid.
+    pub fn new(width: usize, height: usize, seed: u64) -> Self {
+        let chunks_width = width.div_ceil(CHUNK_SIZE);
+        let chunks_height = height.div_ceil(CHUNK_SIZE);
+        let wind_width = width.div_ceil(WIND_CELL_SIZE);
+        let wind_height = height.div_ceil(WIND_CELL_SIZE);
+
+        let mut chunks = Vec::with_capacity(chunks_width * chunks_height);
+        for chunk_y in 0..chunks_height {
+            for chunk_x in 0..chunks_width {
+                let min_x = chunk_x * CHUNK_SIZE;
+                let min_y = chunk_y * CHUNK_SIZE;
+                chunks.push(Chunk::new(DirtyRect::new(
+                    min_x,
+                    min_y,
+                    (min_x + CHUNK_SIZE).min(width) - 1,
+                    (min_y + CHUNK_SIZE).min(height) - 1,
+                )));
+            }
+        }
+
         Self {
             width,
             height,
+            region: DirtyRect::new(0, 0, width - 1, height - 1),
             particles: vec![None; width * height],
+            chunks_width,
+            chunks,
+            rng: StdRng::seed_from_u64(seed),
+            materials: Arc::new(MaterialTable::default()),
+            ambient_temperature: DEFAULT_AMBIENT_TEMPERATURE,
+            marks: CellMarks::default(),
+            power_sources: BTreeSet::new(),
+            fans: BTreeSet::new(),
+            powered: CellMarks::default(),
+            rigid_bodies: RigidBodies::default(),
+            anchored: CellMarks::default(),
+            wind: vec![Vec2::ZERO; wind_width * wind_height],
+            wind_squares: DirtyRect::new(0, 0, wind_width - 1, wind_height - 1),
+            ambient_wind: Vec2::ZERO,
+            gusts: Vec::new(),
+            deferred_clusters: Vec::new(),
+        }
+    }
+
+    /// Copies `region` into a new sandbox, so it can be stepped on its own.
+    /// Cells outside of the region are considered out of bounds.
+    /// The window gets its own random generator, seeded from the one of the sandbox.
+    pub fn window(&mut self, region: DirtyRect) -> Self {
+        let mut particles = Vec::with_capacity(region.width() * region.height());
+        for y in region.min_y..=region.max_y {
+            let start = self.to_index(region.min_x, y);
+            particles.extend_from_slice(&self.particles[start..start + region.width()]);
+        }
+
+        let wind_squares = DirtyRect::new(
+            region.min_x / WIND_CELL_SIZE,
+            region.min_y / WIND_CELL_SIZE,
+            region.max_x / WIND_CELL_SIZE,
+            region.max_y / WIND_CELL_SIZE,
+        );
+        let mut wind = Vec::with_capacity(wind_squares.width() * wind_squares.height());
+        for square_y in wind_squares.min_y..=wind_squares.max_y {
+            let start = self.to_wind_index(wind_squares.min_x, square_y);
+            wind.extend_from_slice(&self.wind[start..start + wind_squares.width()]);
+        }
+
+        Self {
+            width: self.width,
+            height: self.height,
+            region,
+            particles,
+            chunks_width: self.chunks_width,
+            chunks: self.chunks.iter().map(|c| Chunk::new(c.bounds())).collect(),
+            rng: StdRng::seed_from_u64(self.rng.gen()),
+            materials: self.materials.clone(),
+            ambient_temperature: self.ambient_temperature,
+            marks: CellMarks::default(),
+            power_sources: cells_in(&self.power_sources, &region),
+            fans: cells_in(&self.fans, &region),
+            powered: CellMarks::default(),
+            rigid_bodies: RigidBodies::default(),
+            anchored: CellMarks::default(),
+            wind,
+            wind_squares,
+            ambient_wind: self.ambient_wind,
+            gusts: Vec::new(),
+            deferred_clusters: Vec::new(),
+        }
+    }
+
+    /// Writes back the particles, the woken cells, the gusts and the deferred clusters of a
+    /// window.
+    pub fn merge_window(&mut self, window: Sandbox) {
+        let region = window.region;
+        for y in region.min_y..=region.max_y {
+            let start = self.to_index(region.min_x, y);
+            let window_start = window.to_index(region.min_x, y);
+            self.particles[start..start + region.width()]
+                .copy_from_slice(&window.particles[window_start..window_start + region.width()]);
+        }
+
+        self.power_sources
+            .retain(|&(x, y)| window.out_of_bounds_usize(x, y));
+        self.fans.retain(|&(x, y)| window.out_of_bounds_usize(x, y));
+        self.power_sources.extend(window.power_sources);
+        self.fans.extend(window.fans);
+
+        for (chunk, window_chunk) in self.chunks.iter_mut().zip(window.chunks.iter()) {
+            chunk.merge(window_chunk);
         }
+        self.gusts.extend(window.gusts);
+        self.deferred_clusters.extend(window.deferred_clusters);
     }
 
     #[inline]
@@ -24,6 +177,120 @@ impl Sandbox {
         self.particles[index].as_ref()
     }
 
+    /// Takes the marks of the sandbox, cleared for the cells it holds, to mark them while
+    /// reading the sandbox. They are given back with `set_marks`.
+    pub fn take_marks(&mut self) -> CellMarks {
+        let mut marks = std::mem::take(&mut self.marks);
+        marks.clear(self.particles.len());
+        marks
+    }
+
+    pub fn set_marks(&mut self, marks: CellMarks) {
+        self.marks = marks;
+    }
+
+    #[inline]
+    pub fn power_sources(&self) -> &BTreeSet<(usize, usize)> {
+        &self.power_sources
+    }
+
+    #[inline]
+    pub fn fans(&self) -> &BTreeSet<(usize, usize)> {
+        &self.fans
+    }
+
+    /// Whether the cell was connected to a power source during the last electricity update.
+    #[inline]
+    pub fn is_powered(&self, x: usize, y: usize) -> bool {
+        self.powered.is_marked(self.to_index(x, y))
+    }
+
+    /// Takes the powered cells, all unpowered, to power them again. They are given back with
+    /// `set_powered`.
+    pub fn take_powered(&mut self) -> CellMarks {
+        let mut powered = std::mem::take(&mut self.powered);
+        powered.clear(self.particles.len());
+        powered
+    }
+
+    pub fn set_powered(&mut self, powered: CellMarks) {
+        self.powered = powered;
+    }
+
+    /// Takes the rigid bodies, to move them while changing the sandbox. They are given back
+    /// with `set_rigid_bodies`.
+    pub fn take_rigid_bodies(&mut self) -> RigidBodies {
+        std::mem::take(&mut self.rigid_bodies)
+    }
+
+    pub fn set_rigid_bodies(&mut self, rigid_bodies: RigidBodies) {
+        self.rigid_bodies = rigid_bodies;
+    }
+
+    /// Forgets the clusters found anchored, so they are searched again.
+    pub fn clear_anchored(&mut self) {
+        self.anchored.clear(self.particles.len());
+    }
+
+    /// Takes the cells found anchored, to search more clusters. They are given back with
+    /// `set_anchored`.
+    pub fn take_anchored(&mut self) -> CellMarks {
+        std::mem::take(&mut self.anchored)
+    }
+
+    pub fn set_anchored(&mut self, anchored: CellMarks) {
+        self.anchored = anchored;
+    }
+
+    /// Wind blowing over the cell, in cells per tick.
+    #[inline]
+    pub fn wind(&self, x: usize, y: usize) -> Vec2 {
+        self.wind[self.to_wind_index(x / WIND_CELL_SIZE, y / WIND_CELL_SIZE)]
+    }
+
+    /// Wind field, `wind_width` squares wide.
+    pub fn wind_field(&self) -> &[Vec2] {
+        &self.wind
+    }
+
+    #[inline]
+    pub fn wind_width(&self) -> usize {
+        self.wind_squares.width()
+    }
+
+    #[inline]
+    fn to_wind_index(&self, square_x: usize, square_y: usize) -> usize {
+        (square_y - self.wind_squares.min_y) * self.wind_squares.width()
+            + (square_x - self.wind_squares.min_x)
+    }
+
+    /// Replaces the wind field, indexed like `wind_field`.
+    pub fn set_wind_field(&mut self, wind: Vec<Vec2>) {
+        debug_assert_eq!(wind.len(), self.wind.len());
+        self.wind = wind;
+    }
+
+    /// Adds a push to the wind blowing over the cell, from the next tick.
+    pub fn push_wind(&mut self, x: usize, y: usize, push: Vec2) {
+        self.gusts.push((x, y, push));
+    }
+
+    /// Removes the pushes added since the last call.
+    pub fn take_gusts(&mut self) -> Vec<(usize, usize, Vec2)> {
+        std::mem::take(&mut self.gusts)
+    }
+
+    /// Searches the cluster of the crumbling particle again on the whole sandbox, at the end of
+    /// the tick.
+    pub fn defer_cluster(&mut self, x: usize, y: usize) {
+        self.deferred_clusters.push((x, y));
+    }
+
+    /// Removes the clusters deferred since the last call.
+    pub fn take_deferred_clusters(&mut self) -> Vec<(usize, usize)> {
+        std::mem::take(&mut self.deferred_clusters)
+    }
+
     #[inline]
     pub fn checked_get(&self, x: usize, y: usize) -> Option<&Particle> {
         if self.out_of_bounds_usize(x, y) {
@@ -59,24 +326,104 @@ impl Sandbox {
 
     #[inline]
     pub fn set(&mut self, x: usize, y: usize, particle: Option<Particle>) {
-        let index = self.to_index(x, y);
-        if index >= self.particles.len() {
+        if self.out_of_bounds_usize(x, y) {
             return;
         }
 
+        let index = self.to_index(x, y);
+        let previous = self.particles[index].map(|particle| particle.material);
         self.particles[index] = particle;
+        self.track(x, y, previous, particle.map(|particle| particle.material));
+        self.wake(x, y);
     }
 
     #[inline]
     pub fn swap(&mut self, x1: usize, y1: usize, x2: usize, y2: usize) {
         let index1 = self.to_index(x1, y1);
         let index2 = self.to_index(x2, y2);
+        let material1 = self.particles[index1].map(|particle| particle.material);
+        let material2 = self.particles[index2].map(|particle| particle.material);
+
+        self.particles.swap(index1, index2);
+        self.track(x1, y1, material1, material2);
+        self.track(x2, y2, material2, material1);
+        self.wake(x1, y1);
+        self.wake(x2, y2);
+    }
+
+    /// Keeps the cells of the power sources and fans up to date, once the material in a cell
+    /// changed.
+    #[inline]
+    fn track(&mut self, x: usize, y: usize, previous: Option<MaterialId>, new: Option<MaterialId>) {
+        if previous == new {
+            return;
+        }
+        if let Some(material) = previous {
+            if self.is_power_source(material) {
+                self.power_sources.remove(&(x, y));
+            }
+            if self.is_fan(material) {
+                self.fans.remove(&(x, y));
+            }
+        }
+        if let Some(material) = new {
+            if self.is_power_source(material) {
+                self.power_sources.insert((x, y));
+            }
+            if self.is_fan(material) {
+                self.fans.insert((x, y));
+            }
+        }
+    }
+
+    #[inline]
+    fn is_power_source(&self, material: MaterialId) -> bool {
+        self.properties(material).electric == Some(Electric::PowerSource)
+    }
+
+    #[inline]
+    fn is_fan(&self, material: MaterialId) -> bool {
+        self.properties(material).fan.is_some()
+    }
+
+    /// Marks the cell and its direct neighbors to be stepped next tick.
+    pub fn wake(&mut self, x: usize, y: usize) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+
+        let rect = DirtyRect::new(
+            x.saturating_sub(1),
+            y.saturating_sub(1),
+            (x + 1).min(self.width - 1),
+            (y + 1).min(self.height - 1),
+        );
 
-        let particle1 = self.get(x1, y1).copied();
-        let particle2 = self.get(x2, y2).copied();
+        for chunk_y in (rect.min_y / CHUNK_SIZE)..=(rect.max_y / CHUNK_SIZE) {
+            for chunk_x in (rect.min_x / CHUNK_SIZE)..=(rect.max_x / CHUNK_SIZE) {
+                self.chunks[chunk_y * self.chunks_width + chunk_x].wake(&rect);
+            }
+        }
+    }
 
-        self.particles[index1] = particle2;
-        self.particles[index2] = particle1;
+    /// Marks the whole sandbox to be stepped next tick.
+    pub fn wake_all(&mut self) {
+        for chunk in self.chunks.iter_mut() {
+            let bounds = chunk.bounds();
+            chunk.wake(&bounds);
+        }
+    }
+
+    /// Makes the changes of the previous tick the area to step during this tick.
+    pub fn update_chunks(&mut self) {
+        for chunk in self.chunks.iter_mut() {
+            chunk.swap_rects();
+        }
+    }
+
+    #[inline]
+    pub fn chunks(&self) -> &[Chunk] {
+        &self.chunks
     }
 
     #[inline]
@@ -87,9 +434,129 @@ impl Sandbox {
         }
     }
 
+    #[inline]
+    pub fn rng(&mut self) -> &mut StdRng {
+        &mut self.rng
+    }
+
+    /// Temperature of the empty cells, particles exchange heat with it.
+    #[inline]
+    pub fn ambient_temperature(&self) -> i32 {
+        self.ambient_temperature
+    }
+
+    pub fn set_ambient_temperature(&mut self, temperature: i32) {
+        self.ambient_temperature = temperature;
+        self.wake_all();
+    }
+
+    /// Wind blowing where nothing else stirs the air.
+    #[inline]
+    pub fn ambient_wind(&self) -> Vec2 {
+        self.ambient_wind
+    }
+
+    pub fn set_ambient_wind(&mut self, wind: Vec2) {
+        self.ambient_wind = wind;
+        self.wake_all();
+    }
+
+    #[inline]
+    pub fn materials(&self) -> &MaterialTable {
+        &self.materials
+    }
+
+    /// Replaces the material table. Particles are matched to the new materials by name,
+    /// and particles of removed materials are deleted.
+    pub fn set_materials(&mut self, materials: Arc<MaterialTable>) {
+        let new_ids: Vec<Option<MaterialId>> = self
+            .materials
+            .iter()
+            .map(|(_, properties)| materials.id(&properties.name))
+            .collect();
+
+        // Colors are picked on spawn, so they are picked again when the material colors change
+        let recolor: Vec<bool> = self
+            .materials
+            .iter()
+            .zip(new_ids.iter())
+            .map(|((_, old), new_id)| match new_id {
+                Some(id) => {
+                    let new = materials.get(*id);
+                    old.colors != new.colors
+                        || old.burn_colors != new.burn_colors
+                        || old.color_variation != new.color_variation
+                }
+                None => false,
+            })
+            .collect();
+
+        for cell in self.particles.iter_mut() {
+            if let Some(particle) = cell {
+                let old_id = particle.material.0 as usize;
+                // The counter of a clone block is the material it copied, matched by name too
+                let old = self.materials.get(particle.material);
+                if old
+                    .emitter
+                    .is_some_and(|emitter| emitter.material.is_none())
+                {
+                    let copied = particle.copied();
+                    particle.set_copied(copied.and_then(|copied| new_ids[copied.0 as usize]));
+                }
+                match new_ids[old_id] {
+                    Some(id) => {
+                        particle.material = id;
+                        if recolor[old_id] {
+                            particle.color = match particle.burning {
+                                true => materials.burn_color(id, &mut self.rng),
+                                false => materials.color(id, &mut self.rng),
+                            };
+                        }
+                    }
+                    None => *cell = None,
+                }
+            }
+        }
+
+        self.materials = materials;
+
+        self.power_sources.clear();
+        self.fans.clear();
+        for y in self.region.min_y..=self.region.max_y {
+            for x in self.region.min_x..=self.region.max_x {
+                if let Some(particle) = self.get(x, y) {
+                    let material = particle.material;
+                    self.track(x, y, None, Some(material));
+                }
+            }
+        }
+        self.wake_all();
+    }
+
+    #[inline]
+    pub fn properties(&self, material: MaterialId) -> &MaterialProperties {
+        self.materials.get(material)
+    }
+
+    #[inline]
+    pub fn get_particle(&mut self, material: MaterialId) -> Particle {
+        self.materials
+            .get_particle(material, self.ambient_temperature, &mut self.rng)
+    }
+
+    #[inline]
+    pub fn color(&mut self, material: MaterialId) -> (u8, u8, u8, u8) {
+        self.materials.color(material, &mut self.rng)
+    }
+
+    #[inline]
+    pub fn burn_color(&mut self, material: MaterialId) -> (u8, u8, u8, u8) {
+        self.materials.burn_color(material, &mut self.rng)
+    }
+
     #[inline]
     pub fn to_index(&self, x: usize, y: usize) -> usize {
-        (y * self.width) + x
+        ((y - self.region.min_y) * self.region.width()) + x - self.region.min_x
     }
 
     #[inline]
@@ -102,19 +569,43 @@ impl Sandbox {
         self.height
     }
 
-    #[inline]
+    /// Every particle moved during a tick woke its cell, so only the areas
+    /// to step next tick need to be reset.
     pub fn reset_updated(&mut self) {
-        for particle in self.particles.iter_mut().filter_map(|x| x.as_mut()) {
-            particle.updated = false;
+        for rect in self.chunks.iter().filter_map(|c| c.next_dirty_rect()) {
+            for y in rect.min_y..=rect.max_y {
+                let start = self.to_index(rect.min_x, y);
+                for particle in self.particles[start..start + rect.width()]
+                    .iter_mut()
+                    .filter_map(|x| x.as_mut())
+                {
+                    particle.updated = false;
+                }
+            }
         }
     }
 
     #[inline]
     pub fn out_of_bounds_i32(&self, x: i32, y: i32) -> bool {
-        x < 0 || x >= self.width as i32 || y < 0 || y >= self.height as i32
+        x < self.region.min_x as i32
+            || x > self.region.max_x as i32
+            || y < self.region.min_y as i32
+            || y > self.region.max_y as i32
     }
     #[inline]
     pub fn out_of_bounds_usize(&self, x: usize, y: usize) -> bool {
-        x >= self.width || y >= self.height
+        x < self.region.min_x
+            || x > self.region.max_x
+            || y < self.region.min_y
+            || y > self.region.max_y
     }
 }
+
+/// Cells of the set inside the region.
+fn cells_in(cells: &BTreeSet<(usize, usize)>, region: &DirtyRect) -> BTreeSet<(usize, usize)> {
+    cells
+        .range((region.min_x, region.min_y)..=(region.max_x, region.max_y))
+        .filter(|&&(_, y)| y >= region.min_y && y <= region.max_y)
+        .copied()
+        .collect()
+}
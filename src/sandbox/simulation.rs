@@ -1,24 +1,150 @@
 use bevy::prelude::*;
-use rand::{thread_rng, Rng};
+use bevy::tasks::{ComputeTaskPool, TaskPool};
+use rand::Rng;
 
 use super::{
-    movement::step_movement, particle::get_particle, sandbox::*, temperature::step_temperature,
+    chunk::{DirtyRect, CHUNK_MARGIN, CHUNK_SIZE},
+    clock::SimulationClock,
+    electricity::update_electricity,
+    emitter::{step_drain, step_emitter},
+    fire::step_flame,
+    growth::step_growth,
+    marks::CellMarks,
+    movement::step_movement,
+    particle::{MovementType, ParticleReplacement},
+    pressure::update_pressure,
+    reaction::step_reactions,
+    rigid_body::update_rigid_bodies,
+    sandbox::*,
+    solution::step_solution,
+    temperature::{replace, round_randomly, step_temperature},
+    wind::update_wind,
 };
 
-pub fn update_particles(mut sandbox_query: Query<&mut Sandbox>) {
+/// How the dirty chunks are stepped every tick.
+#[derive(Resource, Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum UpdateMode {
+    #[default]
+    SingleThreaded,
+    /// Steps non-adjacent chunks in parallel, in four checkerboard passes.
+    /// Particles can't reach further than `CHUNK_MARGIN` outside of their chunk.
+    Checkerboard,
+}
+
+pub fn update_particles(
+    mut sandbox_query: Query<&mut Sandbox>,
+    update_mode: Res<UpdateMode>,
+    clock: Res<SimulationClock>,
+) {
     let mut sandbox = sandbox_query
         .get_single_mut()
         .expect("Sandbox should exists");
 
-    for x in 0..sandbox.width() {
-        for y in 0..sandbox.height() {
-            step_particle(x, y, &mut sandbox);
-        }
+    for _ in 0..clock.ticks_to_run() {
+        step_sandbox(&mut sandbox, *update_mode);
+    }
+}
+
+/// Advances the whole sandbox by one tick.
+pub fn step_sandbox(sandbox: &mut Sandbox, update_mode: UpdateMode) {
+    update_electricity(sandbox);
+    update_wind(sandbox);
+    sandbox.update_chunks();
+    update_pressure(sandbox);
+    update_rigid_bodies(sandbox);
+
+    match update_mode {
+        UpdateMode::SingleThreaded => update_single_threaded(sandbox),
+        UpdateMode::Checkerboard => update_checkerboard(sandbox),
     }
+    update_deferred_clusters(sandbox);
 
     sandbox.reset_updated();
 }
 
+fn update_single_threaded(sandbox: &mut Sandbox) {
+    let dirty_rects: Vec<DirtyRect> = sandbox
+        .chunks()
+        .iter()
+        .filter_map(|chunk| chunk.dirty_rect())
+        .collect();
+
+    for rect in dirty_rects {
+        step_rect(&rect, sandbox);
+    }
+}
+
+fn update_checkerboard(sandbox: &mut Sandbox) {
+    let pool = ComputeTaskPool::get_or_init(TaskPool::default);
+    let (width, height) = (sandbox.width(), sandbox.height());
+
+    for pass in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+        let regions: Vec<(DirtyRect, DirtyRect)> = sandbox
+            .chunks()
+            .iter()
+            .filter(|chunk| {
+                let bounds = chunk.bounds();
+                (
+                    (bounds.min_x / CHUNK_SIZE) % 2,
+                    (bounds.min_y / CHUNK_SIZE) % 2,
+                ) == pass
+            })
+            .filter_map(|chunk| {
+                let rect = chunk.dirty_rect()?;
+                let reach = chunk.bounds().expand(CHUNK_MARGIN, width, height);
+                let region = rect
+                    .expand(CHUNK_MARGIN, width, height)
+                    .intersection(&reach)?;
+                Some((rect, region))
+            })
+            .collect();
+
+        let jobs: Vec<(DirtyRect, Sandbox)> = regions
+            .into_iter()
+            .map(|(rect, region)| (rect, sandbox.window(region)))
+            .collect();
+
+        let windows = pool.scope(|scope| {
+            for (rect, mut window) in jobs {
+                scope.spawn(async move {
+                    step_rect(&rect, &mut window);
+                    window
+                });
+            }
+        });
+
+        for window in windows {
+            sandbox.merge_window(window);
+        }
+    }
+}
+
+/// Searches the clusters going on outside of the windows they were exposed in, on the whole
+/// sandbox.
+fn update_deferred_clusters(sandbox: &mut Sandbox) {
+    let deferred = sandbox.take_deferred_clusters();
+    if deferred.is_empty() {
+        return;
+    }
+
+    sandbox.clear_anchored();
+    for (x, y) in deferred {
+        if sandbox.get(x, y).is_some() {
+            crumble_unanchored(x, y, sandbox);
+        }
+    }
+}
+
+fn step_rect(rect: &DirtyRect, sandbox: &mut Sandbox) {
+    // Particles move between the rects, so the clusters are searched again in each of them
+    sandbox.clear_anchored();
+    for x in rect.min_x..=rect.max_x {
+        for y in rect.min_y..=rect.max_y {
+            step_particle(x, y, sandbox);
+        }
+    }
+}
+
 pub fn step_particle(x: usize, y: usize, sandbox: &mut Sandbox) {
     match sandbox.get(x, y) {
         Some(p) if p.updated || p.health <= 0 => {
@@ -31,7 +157,17 @@ pub fn step_particle(x: usize, y: usize, sandbox: &mut Sandbox) {
         _ => {}
     }
 
-    if step_acidity(x, y, sandbox) || step_temperature(x, y, sandbox) || step_health(x, y, sandbox)
+    if step_reactions(x, y, sandbox)
+        || step_acidity(x, y, sandbox)
+        || step_solution(x, y, sandbox)
+        || step_integrity(x, y, sandbox)
+        || step_flame(x, y, sandbox)
+        || step_temperature(x, y, sandbox)
+        || step_health(x, y, sandbox)
+        || step_packing(x, y, sandbox)
+        || step_growth(x, y, sandbox)
+        || step_emitter(x, y, sandbox)
+        || step_drain(x, y, sandbox)
     {
         return;
     }
@@ -45,17 +181,21 @@ pub fn step_health(x: usize, y: usize, sandbox: &mut Sandbox) -> bool {
         None => return false,
     };
 
-    let replacement = match &particle.lifespan {
+    let replacement = match sandbox.properties(particle.material).lifespan {
         Some(lifespan) => lifespan.replacement,
         None => return false,
     };
 
     let health = &mut sandbox.get_mut(x, y).unwrap().health;
     *health -= 1;
+    let health = *health;
+    sandbox.wake(x, y);
 
-    if *health <= 0 {
-        if thread_rng().gen_bool(replacement.probability.into()) {
-            let replacement = replacement.material.map(get_particle);
+    if health <= 0 {
+        if sandbox.rng().gen_bool(replacement.probability.into()) {
+            let replacement = replacement
+                .material
+                .map(|material| sandbox.get_particle(material));
             sandbox.set(x, y, replacement);
         }
         return true;
@@ -63,13 +203,40 @@ pub fn step_health(x: usize, y: usize, sandbox: &mut Sandbox) -> bool {
     false
 }
 
+/// Compresses a particle buried under enough particles, like snow packing into ice.
+pub fn step_packing(x: usize, y: usize, sandbox: &mut Sandbox) -> bool {
+    let particle = *sandbox.get(x, y).unwrap();
+    let packing = match sandbox.properties(particle.material).packing {
+        Some(packing) => packing,
+        None => return false,
+    };
+
+    for depth in 1..=packing.depth {
+        if sandbox.checked_get(x, y + depth).is_none() {
+            return false;
+        }
+    }
+
+    if !sandbox.rng().gen_bool(packing.probability as f64) {
+        return false;
+    }
+
+    let mut packed = sandbox.get_particle(packing.material);
+    packed.temperature = particle.temperature;
+    sandbox.set(x, y, Some(packed));
+    true
+}
+
+/// Dissolves the corrodable neighbors of an acid, according to their corrosion resistance, and
+/// neutralizes acids and bases touching each other. Returns true if the particle was replaced.
 pub fn step_acidity(x: usize, y: usize, sandbox: &mut Sandbox) -> bool {
-    let acidity = match sandbox.get(x, y).unwrap().acidity {
+    let particle = *sandbox.get(x, y).unwrap();
+    let acidity = match sandbox.properties(particle.material).acidity {
         Some(a) => a.0,
         None => return false,
     };
 
-    if acidity <= 0 {
+    if acidity == 0 {
         return false;
     }
 
@@ -80,15 +247,54 @@ pub fn step_acidity(x: usize, y: usize, sandbox: &mut Sandbox) -> bool {
         (x, y.overflowing_sub(1).0),
         (x, y + 1),
     ] {
-        if let Some(particle) = sandbox.checked_get_mut(neighbor_x, neighbor_y) {
-            if let Some(corrodable) = particle.corrodable.as_mut() {
-                corrodable.0 -= acidity;
-                acid_ticks += 1;
+        let neighbor = match sandbox.checked_get(neighbor_x, neighbor_y) {
+            Some(neighbor) => *neighbor,
+            None => continue,
+        };
+        let properties = sandbox.properties(neighbor.material);
 
-                if corrodable.0 <= 0 {
-                    sandbox.set(neighbor_x, neighbor_y, None);
-                }
+        // An acid and a base neutralize each other
+        if properties
+            .acidity
+            .is_some_and(|other| other.0.signum() == -acidity.signum())
+        {
+            let neutralized = properties.neutralized;
+            let own_neutralized = sandbox.properties(particle.material).neutralized;
+            neutralize(neighbor_x, neighbor_y, neutralized, sandbox);
+            neutralize(x, y, own_neutralized, sandbox);
+            return true;
+        }
+
+        // Bases don't corrode
+        if acidity < 0 || !properties.corrodable {
+            continue;
+        }
+
+        let (resistance, residue) = (
+            properties.corrosion_resistance,
+            properties.corrosion_residue,
+        );
+        let damage = round_randomly(acidity as f32 * (1.0 - resistance), sandbox);
+        if damage <= 0 {
+            continue;
+        }
+        acid_ticks += 1;
+
+        let health = &mut sandbox.get_mut(neighbor_x, neighbor_y).unwrap().health;
+        *health -= damage;
+        if *health <= 0 {
+            match residue {
+                Some(residue) => replace(
+                    neighbor_x,
+                    neighbor_y,
+                    residue,
+                    neighbor.temperature,
+                    sandbox,
+                ),
+                None => sandbox.set(neighbor_x, neighbor_y, None),
             }
+        } else {
+            sandbox.wake(neighbor_x, neighbor_y);
         }
     }
     let acid_health = &mut sandbox.get_mut(x, y).unwrap().health;
@@ -101,3 +307,163 @@ pub fn step_acidity(x: usize, y: usize, sandbox: &mut Sandbox) -> bool {
 
     false
 }
+
+/// Replaces a neutralized acid or base, keeping its temperature.
+fn neutralize(x: usize, y: usize, neutralized: Option<ParticleReplacement>, sandbox: &mut Sandbox) {
+    let temperature = sandbox.get(x, y).unwrap().temperature;
+    match neutralized {
+        Some(neutralized) => replace(x, y, neutralized, temperature, sandbox),
+        None => sandbox.set(x, y, None),
+    }
+}
+
+/// Crumbles a solid into its falling debris, along with the crumbling solids connected to it,
+/// once none of them holds to an anchor anymore: the border of the sandbox, another solid, or
+/// the powders they rest on. Other solids are anchored, or hold on their own.
+pub fn step_integrity(x: usize, y: usize, sandbox: &mut Sandbox) -> bool {
+    let material = sandbox.get(x, y).unwrap().material;
+    if sandbox.properties(material).crumble.is_none() {
+        return false;
+    }
+
+    // A cluster only loses its anchor when one of its particles is removed, which exposes the
+    // particles around it
+    let exposed = [
+        (x.overflowing_sub(1).0, y),
+        (x + 1, y),
+        (x, y.overflowing_sub(1).0),
+        (x, y + 1),
+    ]
+    .into_iter()
+    .any(|(neighbor_x, neighbor_y)| {
+        !sandbox.out_of_bounds_usize(neighbor_x, neighbor_y)
+            && !is_solid(neighbor_x, neighbor_y, sandbox)
+    });
+    if !exposed {
+        return false;
+    }
+    crumble_unanchored(x, y, sandbox)
+}
+
+/// Crumbles the cluster of a crumbling solid if it holds to no anchor. Returns true if it did.
+fn crumble_unanchored(x: usize, y: usize, sandbox: &mut Sandbox) -> bool {
+    let material = sandbox.get(x, y).unwrap().material;
+    if sandbox.properties(material).crumble.is_none() {
+        return false;
+    }
+
+    let mut anchored = sandbox.take_anchored();
+    let support = find_support(x, y, &mut anchored, sandbox);
+    sandbox.set_anchored(anchored);
+    let cluster = match support {
+        Support::Unanchored(cluster) => cluster,
+        Support::Anchored => return false,
+        Support::OutsideWindow => {
+            sandbox.defer_cluster(x, y);
+            return false;
+        }
+    };
+
+    for (cluster_x, cluster_y) in cluster {
+        let particle = *sandbox.get(cluster_x, cluster_y).unwrap();
+        let crumble = sandbox.properties(particle.material).crumble.unwrap();
+        let mut debris = sandbox.get_particle(crumble);
+        debris.temperature = particle.temperature;
+        sandbox.set(cluster_x, cluster_y, Some(debris));
+    }
+    true
+}
+
+fn is_solid(x: usize, y: usize, sandbox: &Sandbox) -> bool {
+    sandbox.checked_get(x, y).is_some_and(|particle| {
+        sandbox.properties(particle.material).movement_type == MovementType::Solid
+    })
+}
+
+enum Support {
+    Anchored,
+    /// The crumbling solids of the cluster.
+    Unanchored(Vec<(usize, usize)>),
+    /// The cluster goes on outside of the window being stepped.
+    OutsideWindow,
+}
+
+/// Searches the crumbling solids connected to the particle, diagonals included, for one holding
+/// to an anchor. The cells below are searched first, since that's where the anchor usually is.
+///
+/// The cells searched are marked in `anchored` with the cell the search started from. An
+/// unanchored cluster crumbles right away, and one going on outside of the window is searched
+/// again on the whole sandbox, so reaching a cell marked by another search means the cluster
+/// was already found anchored or deferred, and isn't searched again.
+fn find_support(x: usize, y: usize, anchored: &mut CellMarks, sandbox: &Sandbox) -> Support {
+    let (width, height) = (sandbox.width(), sandbox.height());
+    let start = sandbox.to_index(x, y);
+    if anchored.is_marked(start) {
+        return Support::Anchored;
+    }
+    let search = start as u32;
+    anchored.mark(start, search);
+
+    let mut cluster = Vec::new();
+    let mut stack = vec![(x, y)];
+
+    while let Some((x, y)) = stack.pop() {
+        cluster.push((x, y));
+        if x == 0 || y == 0 || x == width - 1 || y == height - 1 {
+            return Support::Anchored;
+        }
+
+        let particle = sandbox.get(x, y).unwrap();
+        if sandbox.properties(particle.material).anchored {
+            return Support::Anchored;
+        }
+        if sandbox.checked_get(x, y - 1).is_some_and(|below| {
+            sandbox.properties(below.material).movement_type == MovementType::Powder
+        }) {
+            return Support::Anchored;
+        }
+
+        // Pushed last, so popped first
+        for (offset_x, offset_y) in [
+            (0, 1),
+            (-1, 1),
+            (1, 1),
+            (-1, 0),
+            (1, 0),
+            (-1, -1),
+            (1, -1),
+            (0, -1),
+        ] {
+            let (neighbor_x, neighbor_y) = (
+                (x as i32 + offset_x) as usize,
+                (y as i32 + offset_y) as usize,
+            );
+            if sandbox.out_of_bounds_usize(neighbor_x, neighbor_y) {
+                return Support::OutsideWindow;
+            }
+
+            let properties = match sandbox.get(neighbor_x, neighbor_y) {
+                Some(neighbor) => sandbox.properties(neighbor.material),
+                None => continue,
+            };
+            if properties.movement_type != MovementType::Solid {
+                continue;
+            }
+            // Any other solid holds the cluster
+            if properties.crumble.is_none() {
+                return Support::Anchored;
+            }
+
+            let neighbor = sandbox.to_index(neighbor_x, neighbor_y);
+            match anchored.get(neighbor) {
+                Some(mark) if mark != search => return Support::Anchored,
+                Some(_) => {}
+                None => {
+                    anchored.mark(neighbor, search);
+                    stack.push((neighbor_x, neighbor_y));
+                }
+            }
+        }
+    }
+    Support::Unanchored(cluster)
+}
@@ -0,0 +1,168 @@
+use rand::Rng;
+
+use super::particle::{MaterialId, Soluble, Solution};
+use super::sandbox::Sandbox;
+
+/// Dissolves soluble powders into the solvent or solution they touch, and spreads the solute
+/// of solutions to their more dilute neighbors. Returns true if the particle was replaced.
+pub fn step_solution(x: usize, y: usize, sandbox: &mut Sandbox) -> bool {
+    let material = sandbox.get(x, y).unwrap().material;
+    let properties = sandbox.properties(material);
+
+    if let Some(soluble) = properties.soluble {
+        return step_dissolving(x, y, soluble, sandbox);
+    }
+
+    if let Some(solution) = properties.solution {
+        return step_diffusion(x, y, solution, sandbox);
+    }
+    false
+}
+
+fn step_dissolving(x: usize, y: usize, soluble: Soluble, sandbox: &mut Sandbox) -> bool {
+    let solution = match sandbox.properties(soluble.solution).solution {
+        Some(solution) => solution,
+        None => return false,
+    };
+
+    for (neighbor_x, neighbor_y) in [
+        (x, y.overflowing_sub(1).0),
+        (x.overflowing_sub(1).0, y),
+        (x + 1, y),
+        (x, y + 1),
+    ] {
+        let concentration = match sandbox.checked_get(neighbor_x, neighbor_y) {
+            Some(neighbor) if neighbor.material == soluble.solution => neighbor.counter,
+            Some(neighbor) if neighbor.material == solution.solvent => 0,
+            _ => continue,
+        };
+        if concentration >= solution.capacity {
+            continue;
+        }
+
+        // Powder resting in a liquid doesn't always get woken up, so it stays awake until
+        // it dissolves
+        sandbox.wake(x, y);
+        if !sandbox.rng().gen_bool(soluble.probability as f64) {
+            return false;
+        }
+
+        add_solute(neighbor_x, neighbor_y, soluble.solution, sandbox);
+        sandbox.set(x, y, None);
+        return true;
+    }
+    false
+}
+
+/// Shares a unit of solute with the most dilute neighbor: its solvent, or a particle of the
+/// same solution holding at least two units less. Returns true if the particle shared its last
+/// unit and turned back into its solvent.
+fn step_diffusion(x: usize, y: usize, solution: Solution, sandbox: &mut Sandbox) -> bool {
+    let particle = *sandbox.get(x, y).unwrap();
+
+    let mut target = None;
+    for (neighbor_x, neighbor_y) in [
+        (x.overflowing_sub(1).0, y),
+        (x + 1, y),
+        (x, y.overflowing_sub(1).0),
+        (x, y + 1),
+    ] {
+        // Sharing with a particle holding one unit less would only swap their concentrations,
+        // but the last unit still spreads into the solvent
+        let (concentration, gap) = match sandbox.checked_get(neighbor_x, neighbor_y) {
+            Some(neighbor) if neighbor.material == particle.material => (neighbor.counter, 2),
+            Some(neighbor) if neighbor.material == solution.solvent => (0, 1),
+            _ => continue,
+        };
+        if concentration > particle.counter - gap {
+            continue;
+        }
+        if target.is_none_or(|(_, _, lowest)| concentration < lowest) {
+            target = Some((neighbor_x, neighbor_y, concentration));
+        }
+    }
+
+    let (target_x, target_y) = match target {
+        Some((target_x, target_y, _)) => (target_x, target_y),
+        None => return false,
+    };
+
+    sandbox.wake(x, y);
+    if !sandbox.rng().gen_bool(solution.diffusion as f64) {
+        return false;
+    }
+
+    add_solute(target_x, target_y, particle.material, sandbox);
+    remove_solute(x, y, solution, sandbox)
+}
+
+/// Hands the solute of an evaporating solution to the neighboring particles of the same
+/// solution, which get more concentrated. The solute left once they are saturated falls out
+/// as a powder instead of evaporating. Returns true if the particle was replaced by it.
+pub fn evaporate(x: usize, y: usize, sandbox: &mut Sandbox) -> bool {
+    let particle = *sandbox.get(x, y).unwrap();
+    let solution = match sandbox.properties(particle.material).solution {
+        Some(solution) => solution,
+        None => return false,
+    };
+
+    let mut concentration = particle.counter;
+    for (neighbor_x, neighbor_y) in [
+        (x, y.overflowing_sub(1).0),
+        (x.overflowing_sub(1).0, y),
+        (x + 1, y),
+        (x, y + 1),
+    ] {
+        if concentration <= 0 {
+            return false;
+        }
+
+        let neighbor = match sandbox.checked_get_mut(neighbor_x, neighbor_y) {
+            Some(neighbor) if neighbor.material == particle.material => neighbor,
+            _ => continue,
+        };
+        let shared = (solution.capacity - neighbor.counter).clamp(0, concentration);
+        neighbor.counter += shared;
+        concentration -= shared;
+        sandbox.wake(neighbor_x, neighbor_y);
+    }
+    if concentration <= 0 {
+        return false;
+    }
+
+    let mut solute = sandbox.get_particle(solution.solute);
+    solute.temperature = particle.temperature;
+    sandbox.set(x, y, Some(solute));
+    true
+}
+
+/// Adds a unit of solute to a particle of a solution, or of its solvent which turns into it.
+fn add_solute(x: usize, y: usize, solution: MaterialId, sandbox: &mut Sandbox) {
+    let particle = *sandbox.get(x, y).unwrap();
+    if particle.material != solution {
+        let mut dissolved = sandbox.get_particle(solution);
+        dissolved.temperature = particle.temperature;
+        dissolved.velocity = particle.velocity;
+        dissolved.counter = 0;
+        sandbox.set(x, y, Some(dissolved));
+    }
+    sandbox.get_mut(x, y).unwrap().counter += 1;
+    sandbox.wake(x, y);
+}
+
+/// Removes a unit of solute from a particle of a solution, which turns back into its solvent
+/// once it holds none. Returns true if the particle was replaced.
+fn remove_solute(x: usize, y: usize, solution: Solution, sandbox: &mut Sandbox) -> bool {
+    let particle = sandbox.get_mut(x, y).unwrap();
+    particle.counter -= 1;
+    if particle.counter > 0 {
+        return false;
+    }
+
+    let particle = *particle;
+    let mut solvent = sandbox.get_particle(solution.solvent);
+    solvent.temperature = particle.temperature;
+    solvent.velocity = particle.velocity;
+    sandbox.set(x, y, Some(solvent));
+    true
+}
@@ -1,25 +1,131 @@
 use bevy::prelude::Vec2;
-use rand::{thread_rng, Rng};
+use rand::Rng;
 
 use super::{
-    particle::{get_particle, Material, TemperatureChanger, Velocity},
+    fire::exposure,
+    particle::{Explosion, MovementType, ParticleReplacement, Velocity},
     sandbox::Sandbox,
+    solution::evaporate,
+    wind::blast_wind,
 };
 
+/// Conductivity of the empty cells, which hold the ambient temperature.
+const AIR_CONDUCTIVITY: f32 = 0.02;
+/// Largest share of a temperature difference a single exchange closes. A particle exchanges
+/// heat on its four sides every tick, so it can't overshoot the temperature of its neighbors.
+const MAX_EXCHANGE: f32 = 0.25;
+/// Heat a burning particle releases into itself every tick.
+const COMBUSTION_HEAT: i32 = 5;
+/// Fewest ticks before an explosive caught in a blast goes off, up to twice as many.
+const CHAIN_DELAY: i32 = 3;
+/// Speed given to the particles next to an explosion, in cells per tick.
+const BLAST_SPEED: f32 = 10.0;
+/// Share of the cells cleared at the center of a blast filled with its debris.
+const DEBRIS_SHARE: f32 = 0.4;
+
 pub fn step_temperature(x: usize, y: usize, sandbox: &mut Sandbox) -> bool {
+    conduct_heat(x, y, sandbox);
     apply_temperature_to_neighbors(x, y, sandbox);
 
-    if step_self(x, y, sandbox) {
+    if step_self(x, y, sandbox) || step_burning(x, y, sandbox) {
         return true;
     }
 
-    step_burning(x, y, sandbox);
     spark_if_ignited(x, y, sandbox);
     false
 }
 
+/// Exchanges heat with the empty cells around the particle, and with its right and top
+/// neighbors, so every pair of particles exchanges heat once per tick.
+pub fn conduct_heat(x: usize, y: usize, sandbox: &mut Sandbox) {
+    let particle = *sandbox.get(x, y).unwrap();
+    let properties = sandbox.properties(particle.material);
+    let (conductivity, heat_capacity) = (properties.conductivity, properties.heat_capacity);
+    if conductivity <= 0.0 {
+        return;
+    }
+
+    let mut heat = 0.0;
+
+    let ambient_temperature = sandbox.ambient_temperature();
+    for (neighbor_x, neighbor_y) in [
+        (x.overflowing_sub(1).0, y),
+        (x + 1, y),
+        (x, y.overflowing_sub(1).0),
+        (x, y + 1),
+    ] {
+        if sandbox.checked_get(neighbor_x, neighbor_y).is_none()
+            && !sandbox.out_of_bounds_usize(neighbor_x, neighbor_y)
+        {
+            // The air holds the ambient temperature, as if its heat capacity was endless
+            let rate = conductivity
+                .min(AIR_CONDUCTIVITY)
+                .min(MAX_EXCHANGE * heat_capacity);
+            heat += (ambient_temperature - particle.temperature) as f32 * rate;
+        }
+    }
+
+    for (neighbor_x, neighbor_y) in [(x + 1, y), (x, y + 1)] {
+        let neighbor = match sandbox.checked_get(neighbor_x, neighbor_y) {
+            Some(neighbor) => *neighbor,
+            None => continue,
+        };
+        let neighbor_properties = sandbox.properties(neighbor.material);
+        let rate = exchange_rate(
+            conductivity.min(neighbor_properties.conductivity),
+            heat_capacity,
+            neighbor_properties.heat_capacity,
+        );
+        let flow = (neighbor.temperature - particle.temperature) as f32 * rate;
+        if flow == 0.0 {
+            continue;
+        }
+
+        let change = round_randomly(-flow / neighbor_properties.heat_capacity, sandbox);
+        if change != 0 {
+            sandbox.get_mut(neighbor_x, neighbor_y).unwrap().temperature += change;
+            sandbox.wake(neighbor_x, neighbor_y);
+        }
+        heat += flow;
+    }
+
+    let change = round_randomly(heat / heat_capacity, sandbox);
+    if change != 0 {
+        sandbox.get_mut(x, y).unwrap().temperature += change;
+        sandbox.wake(x, y);
+    }
+}
+
+/// Share of the temperature difference flowing between two particles, as heat, every tick.
+/// It is limited so that neither particle closes more than `MAX_EXCHANGE` of the difference,
+/// whatever the ratio of the conductivity to the heat capacities.
+fn exchange_rate(conductivity: f32, heat_capacity: f32, other_heat_capacity: f32) -> f32 {
+    let total_capacity = heat_capacity + other_heat_capacity;
+    if total_capacity <= 0.0 {
+        return 0.0;
+    }
+    // Heat closing the whole difference, once both particles reach the same temperature
+    let equalizing = heat_capacity * other_heat_capacity / total_capacity;
+    conductivity.min(MAX_EXCHANGE * equalizing)
+}
+
+/// Temperatures and velocities are integers, small changes are rounded up or down randomly so
+/// they still add up over time.
+pub fn round_randomly(value: f32, sandbox: &mut Sandbox) -> i32 {
+    let floor = value.floor();
+    let fraction = (value - floor) as f64;
+    if fraction > 0.0 && sandbox.rng().gen_bool(fraction) {
+        return floor as i32 + 1;
+    }
+    floor as i32
+}
+
+/// Heats or cools the neighbors by the `TemperatureChanger` of the particle. Neither brings a
+/// neighbor past the temperature of the particle itself.
 pub fn apply_temperature_to_neighbors(x: usize, y: usize, sandbox: &mut Sandbox) {
-    let temp_changer = match sandbox.get(x, y).unwrap().temperature_changer {
+    let particle = sandbox.get(x, y).unwrap();
+    let own_temperature = particle.temperature;
+    let temp_changer = match particle.temperature_changer(sandbox.properties(particle.material)) {
         Some(changer) => changer.0,
         None => return,
     };
@@ -30,117 +136,237 @@ pub fn apply_temperature_to_neighbors(x: usize, y: usize, sandbox: &mut Sandbox)
         (x, y.overflowing_sub(1).0),
         (x, y + 1),
     ] {
-        if let Some(particle) = sandbox.checked_get_mut(neighbor_x, neighbor_y) {
-            if let Some(temperature) = &mut particle.temperature {
-                if temp_changer.is_positive() && !temperature.heatable {
+        let temperature = match sandbox.checked_get(neighbor_x, neighbor_y) {
+            Some(particle) => sandbox.properties(particle.material).temperature,
+            None => None,
+        };
+
+        if let Some(temperature) = temperature {
+            if temp_changer.is_positive() && !temperature.heatable {
+                continue;
+            }
+            if temp_changer.is_negative() && !temperature.coolable {
+                continue;
+            }
+
+            let neighbor = sandbox.get_mut(neighbor_x, neighbor_y).unwrap();
+            if temp_changer.is_negative() {
+                if neighbor.temperature <= own_temperature {
                     continue;
                 }
-                if temp_changer.is_negative() && !temperature.coolable {
+                neighbor.temperature = (neighbor.temperature + temp_changer).max(own_temperature);
+            } else {
+                if neighbor.temperature >= own_temperature {
                     continue;
                 }
-
-                temperature.current = temperature.current + temp_changer;
+                neighbor.temperature = (neighbor.temperature + temp_changer).min(own_temperature);
             }
+            sandbox.wake(neighbor_x, neighbor_y);
         }
     }
 }
 
 fn step_self(x: usize, y: usize, sandbox: &mut Sandbox) -> bool {
-    let temperature = match sandbox.get(x, y).unwrap().temperature {
+    let particle = *sandbox.get(x, y).unwrap();
+    let temperature = match sandbox.properties(particle.material).temperature {
         Some(t) => t,
         None => return false,
     };
 
-    let health = &mut sandbox.get_mut(x, y).unwrap().health;
+    if let Some(explosion) = temperature.explosion {
+        // Primed by a blast nearby, goes off once the fuse burns out
+        if particle.counter > 0 {
+            let fuse = &mut sandbox.get_mut(x, y).unwrap().counter;
+            *fuse -= 1;
+            if *fuse == 0 {
+                explode(x, y, explosion, sandbox);
+                return true;
+            }
+            sandbox.wake(x, y);
+            return false;
+        }
 
-    if (temperature.critical_on_cool && temperature.current <= 0)
-        || (!temperature.critical_on_cool && temperature.current >= 100)
-    {
-        if temperature.explosion_radius > 0 {
-            explode(x, y, temperature.explosion_radius, sandbox);
+        if particle.temperature >= explosion.temperature {
+            explode(x, y, explosion, sandbox);
             return true;
         }
+    }
 
-        *health -= 1;
+    let freezing_drop = particle.freezing_drop(sandbox.properties(particle.material));
+    let transition = match temperature.transition(particle.temperature, freezing_drop) {
+        Some(transition) => transition,
+        None => return false,
+    };
 
-        if *health <= 0 {
-            match temperature.replacement_on_critical.material {
-                Some(mat) => {
-                    if thread_rng().gen_bool(temperature.replacement_on_critical.probability as f64)
-                    {
-                        sandbox.set(x, y, Some(get_particle(mat)));
-                    } else {
-                        sandbox.set(x, y, None);
-                    }
-                }
-                None => sandbox.set(x, y, None),
-            };
-            return true;
+    // Latent heat: the particle has to stay past the threshold for as many ticks as its health
+    let health = &mut sandbox.get_mut(x, y).unwrap().health;
+    *health -= 1;
+    let health = *health;
+    sandbox.wake(x, y);
+
+    if health <= 0 {
+        // Solutions leave their solute behind when they boil away
+        let evaporates = transition.replacement.material.is_some_and(|material| {
+            sandbox.properties(material).movement_type == MovementType::Gas
+        });
+        if !evaporates || !evaporate(x, y, sandbox) {
+            replace(x, y, transition.replacement, particle.temperature, sandbox);
         }
+        return true;
     }
     false
 }
 
-fn explode(cx: usize, cy: usize, radius: i32, sandbox: &mut Sandbox) {
-    let min_x = cx as i32 - radius;
-    let max_x = cx as i32 + radius;
-    let min_y = cy as i32 - radius;
-    let max_y = cy as i32 + radius;
+/// Replaces a particle, the new particle keeps the `temperature` of the old one.
+pub fn replace(
+    x: usize,
+    y: usize,
+    replacement: ParticleReplacement,
+    temperature: i32,
+    sandbox: &mut Sandbox,
+) {
+    let material = match replacement.material {
+        Some(material) if sandbox.rng().gen_bool(replacement.probability as f64) => material,
+        _ => {
+            sandbox.set(x, y, None);
+            return;
+        }
+    };
+
+    let mut particle = sandbox.get_particle(material);
+    particle.temperature = temperature;
+    sandbox.set(x, y, Some(particle));
+}
 
-    for x in (min_x - radius)..=(max_x + radius) {
-        for y in (min_y - radius)..=(max_y + radius) {
+/// Blows up a circle of `radius` around the particle. The blast weakens with the distance:
+/// - particles it is stronger than, by their `blast_resistance`, are destroyed, or crumble,
+/// - the others are heated and flung away, up to twice the radius,
+/// - explosives caught in it are primed, and go off a few ticks later.
+fn explode(cx: usize, cy: usize, explosion: Explosion, sandbox: &mut Sandbox) {
+    let radius = explosion.radius.max(1);
+    let reach = radius * 2;
+    let center = Vec2::new(cx as f32, cy as f32);
+    sandbox.set(cx, cy, None);
+
+    for y in (cy as i32 - reach)..=(cy as i32 + reach) {
+        for x in (cx as i32 - reach)..=(cx as i32 + reach) {
             if sandbox.out_of_bounds_i32(x, y) {
                 continue;
             }
 
-            if let Some(particle) = sandbox.get_mut(x as usize, y as usize) {
-                if x < min_x || x > max_x || y < min_y || y > max_y {
-                    let force = (Vec2::new(x as f32, y as f32) - Vec2::new(cx as f32, cy as f32))
-                        .normalize()
-                        * 10.0;
-                    particle.velocity = Velocity::new(force.x as i32, force.y as i32);
+            let offset = Vec2::new(x as f32, y as f32) - center;
+            let distance = offset.length();
+            if distance > reach as f32 {
+                continue;
+            }
+            let strength = (1.0 - distance / (radius + 1) as f32).max(0.0);
+            let push = 1.0 - distance / (reach + 1) as f32;
+            let (x, y) = (x as usize, y as usize);
+
+            let particle = match sandbox.get(x, y) {
+                Some(particle) => *particle,
+                None => {
+                    if let Some(debris) = explosion.debris {
+                        if sandbox.rng().gen_bool((strength * DEBRIS_SHARE) as f64) {
+                            let debris = sandbox.get_particle(debris);
+                            sandbox.set(x, y, Some(debris));
+                        }
+                    }
                     continue;
                 }
+            };
+            let properties = sandbox.properties(particle.material);
+            let heat = (explosion.heat as f32 * strength) as i32;
 
-                sandbox.set(x as usize, y as usize, Some(get_particle(Material::Spark)));
+            let explosive = properties
+                .temperature
+                .is_some_and(|temperature| temperature.explosion.is_some());
+            if explosive {
+                if strength > 0.0 && particle.counter == 0 {
+                    let fuse = CHAIN_DELAY + sandbox.rng().gen_range(0..=CHAIN_DELAY);
+                    sandbox.get_mut(x, y).unwrap().counter = fuse;
+                }
+            } else if strength > properties.blast_resistance {
+                let debris = properties.crumble.map(|material| {
+                    let mut debris = sandbox.get_particle(material);
+                    debris.temperature = particle.temperature + heat;
+                    debris
+                });
+                sandbox.set(x, y, debris);
+                continue;
             }
+
+            let force = offset.normalize_or_zero() * BLAST_SPEED * push;
+            let particle = sandbox.get_mut(x, y).unwrap();
+            particle.temperature += heat;
+            particle.velocity = Velocity::new(force.x.round() as i32, force.y.round() as i32);
+            sandbox.wake(x, y);
         }
     }
+    blast_wind(cx, cy, radius, sandbox);
 }
 
-fn step_burning(x: usize, y: usize, sandbox: &mut Sandbox) {
-    let particle = sandbox.get_mut(x, y).unwrap();
+fn step_burning(x: usize, y: usize, sandbox: &mut Sandbox) -> bool {
+    let particle = *sandbox.get(x, y).unwrap();
+    let properties = sandbox.properties(particle.material);
 
-    if let Some(burnable) = &mut particle.burnable {
-        let mut temp = particle.temperature.unwrap();
-        if !burnable.burning && temp.current > burnable.burn_temperature {
-            burnable.burning = true;
-            particle.temperature_changer = Some(TemperatureChanger(2));
-            if particle.health < burnable.burn_ticks {
-                particle.health = burnable.burn_ticks;
-            }
-            particle.color = burnable.burn_color;
-        } else if burnable.burning && temp.current < burnable.burn_temperature {
-            burnable.burning = false;
-            particle.temperature_changer = None;
-            particle.color = burnable.cooled_color;
-            temp.current = temp.start_temperature;
+    let (burnable, temperature) = match (properties.burnable, properties.temperature) {
+        (Some(burnable), Some(temperature)) => (burnable, temperature),
+        _ => return false,
+    };
+
+    // Fire needs air, and water puts it out
+    let (air, wet) = exposure(x, y, sandbox);
+
+    if !particle.burning && particle.temperature > burnable.burn_temperature {
+        if !air || wet {
+            return false;
+        }
+        let color = sandbox.burn_color(particle.material);
+        let particle = sandbox.get_mut(x, y).unwrap();
+        particle.burning = true;
+        if particle.health < burnable.burn_ticks {
+            particle.health = burnable.burn_ticks;
+        }
+        particle.color = color;
+    } else if particle.burning && (!air || wet || particle.temperature < burnable.burn_temperature)
+    {
+        let color = sandbox.color(particle.material);
+        let particle = sandbox.get_mut(x, y).unwrap();
+        particle.burning = false;
+        particle.color = color;
+        // A smothered particle stays hot, and catches fire again once exposed to air
+        if air || wet {
+            particle.temperature = temperature.start_temperature;
+        }
+    } else if particle.burning {
+        let particle = sandbox.get_mut(x, y).unwrap();
+        particle.health -= 1;
+        particle.temperature += COMBUSTION_HEAT;
+        if particle.health <= 0 {
+            let temperature = particle.temperature;
+            replace(x, y, burnable.residue, temperature, sandbox);
+            return true;
         }
+    } else {
+        return false;
     }
+    sandbox.wake(x, y);
+    false
 }
 
 fn spark_if_ignited(x: usize, y: usize, sandbox: &mut Sandbox) {
-    let burnable = match sandbox.get_mut(x, y).unwrap().burnable {
-        Some(burnable) => {
-            if !burnable.burning {
-                return;
-            }
-            burnable
-        }
+    let particle = sandbox.get(x, y).unwrap();
+    if !particle.burning {
+        return;
+    }
+
+    let burnable = match sandbox.properties(particle.material).burnable {
+        Some(burnable) => burnable,
         None => return,
     };
 
-    if !burnable.emit_smoke && burnable.emission.is_none() {
+    if burnable.smoke.is_none() && burnable.emission.is_none() {
         return;
     }
 
@@ -150,20 +376,26 @@ fn spark_if_ignited(x: usize, y: usize, sandbox: &mut Sandbox) {
         (x.overflowing_sub(1).0, y),
         (x, y.overflowing_sub(1).0),
     ] {
-        if sandbox.checked_get(neighbor_x, neighbor_y).is_none()
-            && !sandbox.out_of_bounds_usize(neighbor_x, neighbor_y)
-        {
+        // Flames and smoke fill the empty cells, and burn the oxidizers away
+        let free = match sandbox.checked_get(neighbor_x, neighbor_y) {
+            Some(neighbor) => sandbox.properties(neighbor.material).oxidizer,
+            None => !sandbox.out_of_bounds_usize(neighbor_x, neighbor_y),
+        };
+        if free {
             if let Some(material) = burnable.emission {
-                let new_p = if thread_rng().gen_ratio(2, 3) {
-                    get_particle(material)
-                } else if burnable.emit_smoke {
-                    get_particle(Material::Smoke)
+                let new_p = if sandbox.rng().gen_ratio(2, 3) {
+                    sandbox.get_particle(material)
+                } else if let Some(smoke) = burnable.smoke {
+                    sandbox.get_particle(smoke)
                 } else {
                     continue;
                 };
                 sandbox.set(neighbor_x, neighbor_y, Some(new_p));
-            } else if thread_rng().gen_ratio(2, 3) {
-                    sandbox.set(neighbor_x, neighbor_y, Some(get_particle(Material::Smoke)));
+            } else if let Some(smoke) = burnable.smoke {
+                if sandbox.rng().gen_ratio(2, 3) {
+                    let smoke = sandbox.get_particle(smoke);
+                    sandbox.set(neighbor_x, neighbor_y, Some(smoke));
+                }
             }
         }
     }
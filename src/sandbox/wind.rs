@@ -0,0 +1,138 @@
+use bevy::prelude::Vec2;
+
+use super::sandbox::Sandbox;
+
+/// Size of the squares of cells sharing the same wind.
+pub const WIND_CELL_SIZE: usize = 8;
+/// Share of the difference with the ambient wind kept every tick.
+const WIND_DECAY: f32 = 0.95;
+/// Share of the average wind of the neighboring squares taken every tick, so gusts spread.
+const WIND_DIFFUSION: f32 = 0.2;
+/// Strongest wind, in cells per tick.
+const MAX_WIND: f32 = 6.0;
+/// Number of squares a fan blows through, the wind weakens with the distance.
+const FAN_REACH: usize = 4;
+/// Share of the difference with the wind of a fan taken every tick by the squares in front
+/// of it, for every particle of the fan.
+const FAN_BLEND: f32 = 0.5;
+/// Wind blown out of an explosion, next to it.
+const BLAST_WIND: f32 = 6.0;
+
+/// Relaxes the wind toward the ambient wind and spreads it between neighboring squares, then
+/// adds the gusts pushed during the last tick and the wind blown by every fan.
+///
+/// Must run on the whole sandbox, before the chunks are updated.
+pub fn update_wind(sandbox: &mut Sandbox) {
+    let ambient = sandbox.ambient_wind();
+    let wind_width = sandbox.wind_width();
+    let field = sandbox.wind_field();
+    let wind_height = field.len() / wind_width;
+
+    let mut wind: Vec<Vec2> = (0..field.len())
+        .map(|index| {
+            let (square_x, square_y) = (index % wind_width, index / wind_width);
+            let neighbors = [
+                (square_x.wrapping_sub(1), square_y),
+                (square_x + 1, square_y),
+                (square_x, square_y.wrapping_sub(1)),
+                (square_x, square_y + 1),
+            ];
+            // The edges of the field mirror the square itself
+            let average = neighbors
+                .into_iter()
+                .map(|(neighbor_x, neighbor_y)| {
+                    match neighbor_x < wind_width && neighbor_y < wind_height {
+                        true => field[neighbor_y * wind_width + neighbor_x],
+                        false => field[index],
+                    }
+                })
+                .sum::<Vec2>()
+                / 4.0;
+
+            let spread = field[index].lerp(average, WIND_DIFFUSION);
+            ambient + (spread - ambient) * WIND_DECAY
+        })
+        .collect();
+
+    let square = |x: usize, y: usize| (y / WIND_CELL_SIZE) * wind_width + x / WIND_CELL_SIZE;
+    for (x, y, push) in sandbox.take_gusts() {
+        wind[square(x, y)] += push;
+    }
+
+    let (width, height) = (sandbox.width(), sandbox.height());
+    let fans: Vec<(usize, usize)> = sandbox.fans().iter().copied().collect();
+    for (x, y) in fans {
+        let fan = sandbox
+            .properties(sandbox.get(x, y).unwrap().material)
+            .fan
+            .unwrap();
+        let fan = Vec2::new(fan.x, fan.y);
+        let direction = fan.normalize_or_zero();
+        let origin = Vec2::new(x as f32, y as f32) + direction;
+
+        // Only the face of the fan blows
+        if sandbox
+            .checked_get_i32(origin.x.round() as i32, origin.y.round() as i32)
+            .is_some_and(|front| sandbox.properties(front.material).fan.is_some())
+        {
+            continue;
+        }
+
+        for distance in 0..FAN_REACH {
+            let target = origin + direction * (distance * WIND_CELL_SIZE) as f32;
+            if target.x < 0.0 || target.y < 0.0 {
+                break;
+            }
+            let (target_x, target_y) = (target.x as usize, target.y as usize);
+            if target_x >= width || target_y >= height {
+                break;
+            }
+
+            let strength = 1.0 - distance as f32 / FAN_REACH as f32;
+            let index = square(target_x, target_y);
+            wind[index] = wind[index].lerp(fan * strength, FAN_BLEND);
+        }
+
+        // Keeps the particles in front of the fan awake, so the ones at rest get blown away
+        for distance in 1..=(FAN_REACH * WIND_CELL_SIZE) {
+            let target = Vec2::new(x as f32, y as f32) + direction * distance as f32;
+            if target.x < 0.0 || target.y < 0.0 {
+                break;
+            }
+            sandbox.wake(target.x as usize, target.y as usize);
+        }
+    }
+
+    for wind in wind.iter_mut() {
+        *wind = wind.clamp_length_max(MAX_WIND);
+    }
+    sandbox.set_wind_field(wind);
+}
+
+/// Pushes the wind away from an explosion, up to twice its radius.
+pub fn blast_wind(x: usize, y: usize, radius: i32, sandbox: &mut Sandbox) {
+    let reach = (radius * 2).max(1) as f32;
+    let center = Vec2::new(x as f32, y as f32);
+    let (width, height) = (sandbox.width(), sandbox.height());
+
+    let min_x = (x as f32 - reach).max(0.0) as usize / WIND_CELL_SIZE;
+    let max_x = ((x as f32 + reach) as usize).min(width - 1) / WIND_CELL_SIZE;
+    let min_y = (y as f32 - reach).max(0.0) as usize / WIND_CELL_SIZE;
+    let max_y = ((y as f32 + reach) as usize).min(height - 1) / WIND_CELL_SIZE;
+
+    for square_y in min_y..=max_y {
+        for square_x in min_x..=max_x {
+            let (cell_x, cell_y) = (square_x * WIND_CELL_SIZE, square_y * WIND_CELL_SIZE);
+            let square_center =
+                Vec2::new(cell_x as f32, cell_y as f32) + Vec2::splat(WIND_CELL_SIZE as f32 / 2.0);
+            let offset = square_center - center;
+            let distance = offset.length();
+            if distance == 0.0 || distance > reach {
+                continue;
+            }
+
+            let push = offset / distance * BLAST_WIND * (1.0 - distance / reach);
+            sandbox.push_wind(cell_x, cell_y, push);
+        }
+    }
+}
@@ -0,0 +1,34 @@
+mod common;
+
+use common::*;
+use falling_sand::sandbox::*;
+
+#[test]
+fn powered_wire_lights_the_fuse() {
+    for update_mode in UPDATE_MODES {
+        let mut sandbox = Sandbox::new(160, 64, 1);
+        fill(&mut sandbox, "Bedrock", (0, 0), (160, 1));
+        // A battery powering a wire with tnt at its end, and another one with a trail of
+        // gunpowder along it
+        fill(&mut sandbox, "Battery", (10, 1), (11, 2));
+        fill(&mut sandbox, "Metal", (11, 1), (30, 2));
+        fill(&mut sandbox, "Tnt", (30, 1), (31, 2));
+        fill(&mut sandbox, "Battery", (60, 1), (61, 2));
+        fill(&mut sandbox, "Metal", (61, 1), (80, 2));
+        fill(&mut sandbox, "Gunpowder", (65, 2), (75, 3));
+        // A wire with tnt at its end, cut by rubber
+        fill(&mut sandbox, "Battery", (100, 1), (101, 2));
+        fill(&mut sandbox, "Metal", (101, 1), (110, 2));
+        fill(&mut sandbox, "Rubber", (110, 1), (111, 2));
+        fill(&mut sandbox, "Metal", (111, 1), (120, 2));
+        fill(&mut sandbox, "Tnt", (120, 1), (121, 2));
+
+        step(&mut sandbox, update_mode, 300);
+        let tnt: Vec<_> = particles(&sandbox, "Tnt")
+            .into_iter()
+            .map(|(x, y, _)| (x, y))
+            .collect();
+        assert_eq!(count(&sandbox, "Gunpowder"), 0, "{update_mode:?}");
+        assert_eq!(tnt, [(120, 1)], "{update_mode:?}");
+    }
+}
@@ -0,0 +1,64 @@
+//! Helpers shared by the integration tests, each of them uses only some.
+#![allow(dead_code)]
+
+use falling_sand::sandbox::*;
+
+pub const UPDATE_MODES: [UpdateMode; 2] = [UpdateMode::SingleThreaded, UpdateMode::Checkerboard];
+
+pub fn id(sandbox: &Sandbox, material: &str) -> MaterialId {
+    sandbox.materials().id(material).unwrap()
+}
+
+/// Cells of the rect, `min` inclusive and `max` exclusive, row by row.
+pub fn rect(
+    (min_x, min_y): (usize, usize),
+    (max_x, max_y): (usize, usize),
+) -> impl Iterator<Item = (usize, usize)> {
+    (min_y..max_y).flat_map(move |y| (min_x..max_x).map(move |x| (x, y)))
+}
+
+/// Fills the rect with new particles of the material, `min` inclusive and `max` exclusive.
+pub fn fill(sandbox: &mut Sandbox, material: &str, min: (usize, usize), max: (usize, usize)) {
+    let material = id(sandbox, material);
+    for (x, y) in rect(min, max) {
+        let particle = sandbox.get_particle(material);
+        sandbox.set(x, y, Some(particle));
+    }
+}
+
+/// Empties the rect, `min` inclusive and `max` exclusive.
+pub fn clear(sandbox: &mut Sandbox, min: (usize, usize), max: (usize, usize)) {
+    for (x, y) in rect(min, max) {
+        sandbox.set(x, y, None);
+    }
+}
+
+/// Particles of the material, with their cell.
+pub fn particles(sandbox: &Sandbox, material: &str) -> Vec<(usize, usize, Particle)> {
+    let material = id(sandbox, material);
+    rect((0, 0), (sandbox.width(), sandbox.height()))
+        .filter_map(|(x, y)| {
+            sandbox
+                .get(x, y)
+                .filter(|particle| particle.material == material)
+                .map(|particle| (x, y, *particle))
+        })
+        .collect()
+}
+
+pub fn count(sandbox: &Sandbox, material: &str) -> usize {
+    particles(sandbox, material).len()
+}
+
+/// Cells holding a particle in the rect, `min` inclusive and `max` exclusive.
+pub fn filled(sandbox: &Sandbox, min: (usize, usize), max: (usize, usize)) -> usize {
+    rect(min, max)
+        .filter(|&(x, y)| sandbox.get(x, y).is_some())
+        .count()
+}
+
+pub fn step(sandbox: &mut Sandbox, update_mode: UpdateMode, ticks: usize) {
+    for _ in 0..ticks {
+        step_sandbox(sandbox, update_mode);
+    }
+}
@@ -0,0 +1,53 @@
+mod common;
+
+use common::*;
+use falling_sand::sandbox::*;
+
+/// Mean row of the particles of the material.
+fn mean_y(sandbox: &Sandbox, material: &str) -> f32 {
+    let particles = particles(sandbox, material);
+    particles.iter().map(|&(_, y, _)| y as f32).sum::<f32>() / particles.len() as f32
+}
+
+#[test]
+fn liquids_layer_by_density() {
+    for update_mode in UPDATE_MODES {
+        let mut sandbox = Sandbox::new(64, 64, 1);
+        fill(&mut sandbox, "Bedrock", (9, 0), (31, 1));
+        fill(&mut sandbox, "Bedrock", (9, 1), (10, 40));
+        fill(&mut sandbox, "Bedrock", (30, 1), (31, 40));
+        // Stripes of oil, water and acid, the lightest at the bottom
+        for (i, material) in ["Oil", "Water", "Acid"].iter().cycle().take(15).enumerate() {
+            fill(&mut sandbox, material, (10, 1 + i * 2), (30, 3 + i * 2));
+        }
+
+        step(&mut sandbox, update_mode, 600);
+        let (oil, water, acid) = (
+            mean_y(&sandbox, "Oil"),
+            mean_y(&sandbox, "Water"),
+            mean_y(&sandbox, "Acid"),
+        );
+        assert!(
+            oil > water && water > acid,
+            "{update_mode:?}: {oil}, {water}, {acid}"
+        );
+    }
+}
+
+#[test]
+fn gas_bubbles_up_through_liquid() {
+    for update_mode in UPDATE_MODES {
+        let mut sandbox = Sandbox::new(64, 64, 1);
+        fill(&mut sandbox, "Bedrock", (19, 0), (31, 1));
+        fill(&mut sandbox, "Bedrock", (19, 1), (20, 40));
+        fill(&mut sandbox, "Bedrock", (30, 1), (31, 40));
+        fill(&mut sandbox, "Smoke", (20, 1), (30, 3));
+        fill(&mut sandbox, "Water", (20, 3), (30, 30));
+
+        // Smoke doesn't last long, but long enough to come out above the water
+        step(&mut sandbox, update_mode, 10);
+        let smoke = particles(&sandbox, "Smoke");
+        assert_eq!(smoke.len(), 10 * 2, "{update_mode:?}");
+        assert!(smoke.iter().all(|&(_, y, _)| y >= 30), "{update_mode:?}");
+    }
+}
@@ -0,0 +1,59 @@
+mod common;
+
+use common::*;
+use falling_sand::sandbox::*;
+
+/// Size of the sandboxes, a few chunks on each side.
+const SIZE: usize = 160;
+
+/// Builds a sandbox with blocks of sand and water straddling the borders between chunks.
+fn scene(seed: u64) -> Sandbox {
+    let mut sandbox = Sandbox::new(SIZE, SIZE, seed);
+    for (material, min_x, min_y) in [("Sand", 48, 100), ("Water", 100, 40), ("Sand", 110, 120)] {
+        fill(
+            &mut sandbox,
+            material,
+            (min_x, min_y),
+            (min_x + 30, min_y + 30),
+        );
+    }
+    sandbox
+}
+
+/// Material, color, health and temperature of a particle.
+type Cell = (MaterialId, (u8, u8, u8, u8), i32, i32);
+
+/// State of every cell, to compare sandboxes.
+fn snapshot(sandbox: &Sandbox) -> Vec<Option<Cell>> {
+    rect((0, 0), (sandbox.width(), sandbox.height()))
+        .map(|(x, y)| {
+            sandbox
+                .get(x, y)
+                .map(|p| (p.material, p.color, p.health, p.temperature))
+        })
+        .collect()
+}
+
+#[test]
+fn same_seed_gives_same_grid() {
+    for update_mode in UPDATE_MODES {
+        let mut first = scene(42);
+        let mut second = scene(42);
+        step(&mut first, update_mode, 120);
+        step(&mut second, update_mode, 120);
+        assert!(
+            snapshot(&first) == snapshot(&second),
+            "{update_mode:?} grids differ"
+        );
+    }
+}
+
+#[test]
+fn checkerboard_keeps_particles_crossing_chunks() {
+    let mut sandbox = scene(7);
+    let (sand, water) = (count(&sandbox, "Sand"), count(&sandbox, "Water"));
+
+    step(&mut sandbox, UpdateMode::Checkerboard, 120);
+    assert_eq!(count(&sandbox, "Sand"), sand);
+    assert_eq!(count(&sandbox, "Water"), water);
+}
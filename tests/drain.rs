@@ -0,0 +1,25 @@
+mod common;
+
+use common::*;
+use falling_sand::sandbox::*;
+
+#[test]
+fn drain_removes_all_but_drains_and_emitters() {
+    let mut sandbox = Sandbox::new(32, 32, 1);
+    for (x, y, material) in [
+        (16, 16, "Void"),
+        (15, 16, "Bedrock"),
+        (17, 16, "WaterSource"),
+        (16, 17, "Void"),
+        (16, 15, "Clone"),
+    ] {
+        fill(&mut sandbox, material, (x, y), (x + 1, y + 1));
+    }
+
+    step(&mut sandbox, UpdateMode::SingleThreaded, 1);
+    let material = |x, y| sandbox.get(x, y).map(|particle| particle.material);
+    assert_eq!(material(15, 16), None);
+    assert_eq!(material(17, 16), Some(id(&sandbox, "WaterSource")));
+    assert_eq!(material(16, 17), Some(id(&sandbox, "Void")));
+    assert_eq!(material(16, 15), Some(id(&sandbox, "Clone")));
+}
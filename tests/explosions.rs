@@ -0,0 +1,49 @@
+mod common;
+
+use common::*;
+use falling_sand::sandbox::*;
+
+/// Heats the particle past the temperature it explodes at.
+fn detonate(sandbox: &mut Sandbox, x: usize, y: usize) {
+    sandbox.get_mut(x, y).unwrap().temperature = 500;
+    sandbox.wake(x, y);
+}
+
+#[test]
+fn explosives_detonate_in_a_chain() {
+    for update_mode in UPDATE_MODES {
+        let mut sandbox = Sandbox::new(128, 64, 1);
+        fill(&mut sandbox, "Bedrock", (0, 0), (128, 1));
+        for x in [20, 30, 40, 50] {
+            fill(&mut sandbox, "Tnt", (x, 1), (x + 1, 2));
+        }
+        detonate(&mut sandbox, 20, 1);
+
+        // The others only go off a few ticks later, one after the other
+        let mut left = Vec::new();
+        for _ in 0..40 {
+            step(&mut sandbox, update_mode, 1);
+            left.push(count(&sandbox, "Tnt"));
+        }
+        assert_eq!(left[0], 3, "{update_mode:?}");
+        assert!(left.windows(2).all(|w| w[0] - w[1] <= 1), "{update_mode:?}");
+        assert_eq!(left[39], 0, "{update_mode:?}");
+    }
+}
+
+#[test]
+fn blast_resistance_spares_stone() {
+    for update_mode in UPDATE_MODES {
+        let mut sandbox = Sandbox::new(64, 64, 1);
+        fill(&mut sandbox, "Bedrock", (0, 0), (64, 1));
+        // Stone and wood as far from the tnt on either side
+        fill(&mut sandbox, "Stone", (22, 1), (26, 9));
+        fill(&mut sandbox, "Wood", (38, 1), (42, 9));
+        fill(&mut sandbox, "Tnt", (32, 1), (33, 2));
+        detonate(&mut sandbox, 32, 1);
+
+        step(&mut sandbox, update_mode, 2);
+        assert_eq!(count(&sandbox, "Stone"), 4 * 8, "{update_mode:?}");
+        assert_eq!(count(&sandbox, "Wood"), 0, "{update_mode:?}");
+    }
+}
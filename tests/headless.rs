@@ -0,0 +1,32 @@
+use bevy::prelude::*;
+use falling_sand::sandbox::{Sandbox, SimulationClock};
+use falling_sand::{SandboxPlugin, SandboxSettings};
+
+#[test]
+fn plugin_runs_without_rendering() {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        AssetPlugin::default(),
+        SandboxPlugin {
+            settings: SandboxSettings {
+                resolution: Vec2::new(64.0, 64.0),
+                cell_size: 1.0,
+                ..default()
+            },
+        },
+    ));
+    app.update();
+
+    let mut sandbox = app.world.query::<&mut Sandbox>().single_mut(&mut app.world);
+    let sand = sandbox.materials().id("Sand").unwrap();
+    let particle = sandbox.get_particle(sand);
+    sandbox.set(32, 32, Some(particle));
+
+    let mut clock = app.world.resource_mut::<SimulationClock>();
+    clock.pause();
+    clock.step(1);
+    app.update();
+    let sandbox = app.world.query::<&Sandbox>().single(&app.world);
+    assert!(sandbox.get(32, 32).is_none());
+}
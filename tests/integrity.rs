@@ -0,0 +1,44 @@
+mod common;
+
+use common::*;
+use falling_sand::sandbox::*;
+
+#[test]
+fn unanchored_clusters_crumble() {
+    for update_mode in UPDATE_MODES {
+        let mut sandbox = Sandbox::new(128, 64, 1);
+        // A slab held by a pillar standing on the floor, and a floating block
+        fill(&mut sandbox, "Brick", (20, 0), (24, 20));
+        fill(&mut sandbox, "Brick", (10, 20), (34, 23));
+        fill(&mut sandbox, "Brick", (70, 40), (76, 44));
+
+        step(&mut sandbox, update_mode, 10);
+        assert_eq!(count(&sandbox, "Brick"), 4 * 20 + 24 * 3, "{update_mode:?}");
+
+        // Cutting the pillar from the floor crumbles the whole cluster
+        clear(&mut sandbox, (20, 0), (24, 1));
+        step(&mut sandbox, update_mode, 10);
+        assert_eq!(count(&sandbox, "Brick"), 0, "{update_mode:?}");
+    }
+}
+
+#[test]
+fn large_clusters_crumble() {
+    for update_mode in UPDATE_MODES {
+        let mut sandbox = Sandbox::new(192, 128, 1);
+        // A building of more than a thousand bricks spanning several chunks, on a pillar
+        fill(&mut sandbox, "Brick", (90, 0), (94, 40));
+        fill(&mut sandbox, "Brick", (52, 40), (132, 70));
+
+        step(&mut sandbox, update_mode, 10);
+        assert_eq!(
+            count(&sandbox, "Brick"),
+            4 * 40 + 80 * 30,
+            "{update_mode:?}"
+        );
+
+        clear(&mut sandbox, (90, 0), (94, 1));
+        step(&mut sandbox, update_mode, 10);
+        assert_eq!(count(&sandbox, "Brick"), 0, "{update_mode:?}");
+    }
+}
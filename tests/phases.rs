@@ -0,0 +1,42 @@
+mod common;
+
+use common::*;
+use falling_sand::sandbox::*;
+
+/// Keeps the particles and the air at the temperature for a number of ticks.
+fn hold(sandbox: &mut Sandbox, update_mode: UpdateMode, temperature: i32, ticks: usize) {
+    sandbox.set_ambient_temperature(temperature);
+    for _ in 0..ticks {
+        for (x, y) in rect((0, 0), (sandbox.width(), sandbox.height())) {
+            if let Some(particle) = sandbox.get_mut(x, y) {
+                particle.temperature = temperature;
+                sandbox.wake(x, y);
+            }
+        }
+        step_sandbox(sandbox, update_mode);
+    }
+}
+
+#[test]
+fn water_ice_steam_with_hysteresis() {
+    for update_mode in UPDATE_MODES {
+        let mut sandbox = Sandbox::new(32, 32, 1);
+        fill(&mut sandbox, "Water", (8, 0), (24, 1));
+
+        // Each state holds a few degrees past the thresholds before changing back
+        for (temperature, from, to) in [
+            (-3, "Ice", "Water"),
+            (-8, "Water", "Ice"),
+            (3, "Water", "Ice"),
+            (8, "Ice", "Water"),
+            (103, "Steam", "Water"),
+            (110, "Water", "Steam"),
+            (97, "Water", "Steam"),
+            (90, "Steam", "Water"),
+        ] {
+            hold(&mut sandbox, update_mode, temperature, 200);
+            assert_eq!(count(&sandbox, from), 0, "{update_mode:?} at {temperature}");
+            assert!(count(&sandbox, to) > 0, "{update_mode:?} at {temperature}");
+        }
+    }
+}
@@ -0,0 +1,36 @@
+mod common;
+
+use common::*;
+use falling_sand::sandbox::*;
+
+/// Highest row holding water between the columns `min_x` inclusive and `max_x` exclusive.
+fn surface(sandbox: &Sandbox, min_x: usize, max_x: usize) -> usize {
+    particles(sandbox, "Water")
+        .into_iter()
+        .filter(|&(x, _, _)| (min_x..max_x).contains(&x))
+        .map(|(_, y, _)| y)
+        .max()
+        .unwrap()
+}
+
+#[test]
+fn u_tube_levels_out() {
+    for update_mode in UPDATE_MODES {
+        let mut sandbox = Sandbox::new(64, 64, 1);
+        // Two columns joined by a pipe running under the block between them
+        fill(&mut sandbox, "Bedrock", (4, 0), (37, 1));
+        fill(&mut sandbox, "Bedrock", (4, 1), (5, 64));
+        fill(&mut sandbox, "Bedrock", (36, 1), (37, 64));
+        fill(&mut sandbox, "Bedrock", (15, 6), (26, 64));
+        // The left column is filled high, the right one only up to the pipe
+        fill(&mut sandbox, "Water", (5, 1), (15, 40));
+        fill(&mut sandbox, "Water", (15, 1), (36, 6));
+
+        step(&mut sandbox, update_mode, 1500);
+        let (left, right) = (surface(&sandbox, 5, 15), surface(&sandbox, 26, 36));
+        assert!(
+            left.abs_diff(right) <= 3,
+            "{update_mode:?}: {left} and {right}"
+        );
+    }
+}
@@ -0,0 +1,32 @@
+mod common;
+
+use common::*;
+use falling_sand::sandbox::*;
+
+#[test]
+fn body_falls_in_one_piece() {
+    let mut sandbox = Sandbox::new(64, 64, 1);
+    // A pillar in the middle of the floor, and a block above it
+    fill(&mut sandbox, "Stone", (28, 0), (36, 10));
+    fill(&mut sandbox, "Stone", (26, 40), (38, 46));
+
+    step(&mut sandbox, UpdateMode::SingleThreaded, 60);
+    assert_eq!(filled(&sandbox, (26, 40), (38, 46)), 0);
+    assert_eq!(filled(&sandbox, (26, 10), (38, 16)), 12 * 6);
+}
+
+#[test]
+fn body_cut_in_two_breaks_apart() {
+    let mut sandbox = Sandbox::new(64, 64, 1);
+    // A beam resting on a pillar at its left end
+    fill(&mut sandbox, "Stone", (10, 0), (14, 20));
+    fill(&mut sandbox, "Stone", (10, 20), (40, 24));
+    step(&mut sandbox, UpdateMode::SingleThreaded, 10);
+    assert_eq!(filled(&sandbox, (10, 20), (40, 24)), 30 * 4);
+
+    // Cutting the beam lets its right end fall on its own
+    clear(&mut sandbox, (20, 20), (21, 24));
+    step(&mut sandbox, UpdateMode::SingleThreaded, 60);
+    assert_eq!(filled(&sandbox, (10, 20), (20, 24)), 10 * 4);
+    assert_eq!(filled(&sandbox, (21, 20), (40, 24)), 0);
+}
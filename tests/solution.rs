@@ -0,0 +1,47 @@
+mod common;
+
+use common::*;
+use falling_sand::sandbox::*;
+
+/// Concentrations of the particles of salt water.
+fn concentrations(sandbox: &Sandbox) -> Vec<i32> {
+    particles(sandbox, "SaltWater")
+        .into_iter()
+        .map(|(_, _, particle)| particle.counter)
+        .collect()
+}
+
+/// A pool of water with a particle of salt water in the middle.
+fn pool(concentration: i32) -> Sandbox {
+    let mut sandbox = Sandbox::new(32, 32, 1);
+    fill(&mut sandbox, "Water", (0, 0), (32, 10));
+    fill(&mut sandbox, "SaltWater", (16, 5), (17, 6));
+    sandbox.get_mut(16, 5).unwrap().counter = concentration;
+    sandbox
+}
+
+#[test]
+fn solute_spreads_into_the_solvent() {
+    let mut sandbox = pool(6);
+    step(&mut sandbox, UpdateMode::SingleThreaded, 300);
+
+    let concentrations = concentrations(&sandbox);
+    assert_eq!(concentrations.iter().sum::<i32>(), 6);
+    assert!(concentrations.len() > 1);
+    assert!(concentrations
+        .iter()
+        .all(|&concentration| concentration > 0));
+}
+
+#[test]
+fn last_unit_moves_into_the_solvent() {
+    let mut sandbox = pool(1);
+    step(&mut sandbox, UpdateMode::SingleThreaded, 300);
+
+    // The particle holding the unit turned back into water, and another one took it
+    assert_eq!(concentrations(&sandbox), vec![1]);
+    let salt_water = id(&sandbox, "SaltWater");
+    assert!(sandbox
+        .get(16, 5)
+        .is_none_or(|particle| particle.material != salt_water));
+}
@@ -0,0 +1,31 @@
+mod common;
+
+use common::*;
+use falling_sand::sandbox::*;
+
+/// Average column of the particles of the material.
+fn mean_x(sandbox: &Sandbox, material: &str) -> f32 {
+    let particles = particles(sandbox, material);
+    particles.iter().map(|&(x, _, _)| x).sum::<usize>() as f32 / particles.len() as f32
+}
+
+#[test]
+fn fans_blow_particles_away() {
+    for update_mode in UPDATE_MODES {
+        let mut sandbox = Sandbox::new(128, 64, 5);
+        fill(&mut sandbox, "Ash", (30, 0), (50, 4));
+        fill(&mut sandbox, "FanRight", (25, 0), (26, 6));
+        assert_eq!(sandbox.fans().len(), 6);
+
+        let start = mean_x(&sandbox, "Ash");
+        step(&mut sandbox, update_mode, 50);
+        assert!(sandbox.wind(30, 2).x > 1.0, "{update_mode:?}");
+        assert!(mean_x(&sandbox, "Ash") > start + 4.0, "{update_mode:?}");
+
+        // Removed fans stop blowing
+        clear(&mut sandbox, (25, 0), (26, 6));
+        assert!(sandbox.fans().is_empty());
+        step(&mut sandbox, update_mode, 200);
+        assert!(sandbox.wind(30, 2).length() < 0.1, "{update_mode:?}");
+    }
+}